@@ -0,0 +1,3 @@
+cargo build --release --bin frogrs_game
+nohup cargo run --release --bin frogrs_game -- --port 7000 --players local          --players 127.0.0.1:7001 --sim-latency-ms 150 --sim-jitter-ms 20 --sim-loss 0.05 > frogrs.1.log 2>&1 &
+nohup cargo run --release --bin frogrs_game -- --port 7001 --players 127.0.0.1:7000 --players local          --sim-latency-ms 150 --sim-jitter-ms 20 --sim-loss 0.05 > frogrs.2.log 2>&1 &
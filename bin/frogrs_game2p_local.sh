@@ -0,0 +1,2 @@
+cargo build --release --bin frogrs_game
+nohup cargo run --release --bin frogrs_game -- --port 7000 --players local          --players local          > frogrs.1.log 2>&1 &
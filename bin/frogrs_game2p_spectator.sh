@@ -0,0 +1,4 @@
+cargo build --release --bin frogrs_game --bin frogrs_spectate
+nohup cargo run --release --bin frogrs_game -- --port 7000 --players local          --players 127.0.0.1:7001 --spectators 127.0.0.1:7100 > frogrs.1.log 2>&1 &
+nohup cargo run --release --bin frogrs_game -- --port 7001 --players 127.0.0.1:7000 --players local          > frogrs.2.log 2>&1 &
+nohup cargo run --release --bin frogrs_spectate -- --port 7100 --host 127.0.0.1:7000 --players 2 > frogrs_spectate.log 2>&1 &
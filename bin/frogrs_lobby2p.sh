@@ -0,0 +1,4 @@
+cargo build --release --bin frogrs_lobby --bin frogrs_game
+nohup cargo run --release --bin frogrs_lobby -- --port 7777 > frogrs_lobby.log 2>&1 &
+nohup cargo run --release --bin frogrs_game -- --port 7000 --lobby 127.0.0.1:7777 --room-size 2 > frogrs.1.log 2>&1 &
+nohup cargo run --release --bin frogrs_game -- --port 7001 --lobby 127.0.0.1:7777 --room-size 2 > frogrs.2.log 2>&1 &
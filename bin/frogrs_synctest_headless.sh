@@ -0,0 +1,2 @@
+cargo build --release --bin frogrs_synctest
+cargo run --release --bin frogrs_synctest -- --players 2 --check-distance 7 --headless --frames 3600 --seed 42
@@ -1,8 +1,150 @@
+use bevy::ecs::entity::{EntityMap, MapEntities, MapEntitiesError};
 use bevy::prelude::*;
+use bevy::reflect::impl_reflect_value;
+use derive_more::{Deref, DerefMut};
+use serde::{Deserialize, Serialize};
+use std::hash::{Hash, Hasher};
 
-#[derive(Hash, Default, Reflect, Component)]
-#[reflect(Hash)]
+use crate::game::core::maths::structs::Fixed;
+
+/// Something happening on a frame of a clip, fired once each time the frame is shown.
+#[derive(Clone, Copy, Debug, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnimationEvent {
+    Footstep,
+    Takeoff,
+    Land,
+}
+
+/// Frames of a sprite sheet played in order.
+pub struct AnimationClip {
+    /// Index of the first frame in the texture atlas.
+    pub first: usize,
+    pub len: u8,
+    /// How long each frame is shown.
+    pub frame_secs: Fixed,
+    /// One-shot clips stop on their last frame.
+    pub looping: bool,
+    /// Events fired on frames of the clip, frame indices are relative to the clip.
+    pub events: &'static [(u8, AnimationEvent)],
+}
+
+// the frog only has the 20 frames of Stand.png, every clip is a range of it until the other sheets are drawn
+const IDLE_CLIP: AnimationClip = AnimationClip {
+    first: 0,
+    len: 8,
+    frame_secs: Fixed::from_ratio(1, 10),
+    looping: true,
+    events: &[],
+};
+const RUN_CLIP: AnimationClip = AnimationClip {
+    first: 8,
+    len: 6,
+    frame_secs: Fixed::from_ratio(1, 15),
+    looping: true,
+    events: &[(1, AnimationEvent::Footstep), (4, AnimationEvent::Footstep)],
+};
+const JUMP_CLIP: AnimationClip = AnimationClip {
+    first: 14,
+    len: 2,
+    frame_secs: Fixed::from_ratio(1, 12),
+    looping: false,
+    events: &[(0, AnimationEvent::Takeoff)],
+};
+const FALL_CLIP: AnimationClip = AnimationClip {
+    first: 16,
+    len: 2,
+    frame_secs: Fixed::from_ratio(1, 10),
+    looping: true,
+    events: &[],
+};
+const LAND_CLIP: AnimationClip = AnimationClip {
+    first: 18,
+    len: 2,
+    frame_secs: Fixed::from_ratio(1, 20),
+    looping: false,
+    events: &[(0, AnimationEvent::Land)],
+};
+
+/// States of the frog animation state machine, each plays its own clip.
+#[derive(Clone, Copy, Debug, Default, Hash, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnimationState {
+    #[default]
+    Idle,
+    Run,
+    Jump,
+    Fall,
+    Land,
+}
+
+impl AnimationState {
+    pub fn clip(self) -> &'static AnimationClip {
+        match self {
+            AnimationState::Idle => &IDLE_CLIP,
+            AnimationState::Run => &RUN_CLIP,
+            AnimationState::Jump => &JUMP_CLIP,
+            AnimationState::Fall => &FALL_CLIP,
+            AnimationState::Land => &LAND_CLIP,
+        }
+    }
+}
+
+/// Animation of a sprite sheet, part of the rollback state so the events fire the same on every peer.
+#[derive(Clone, Copy, Debug, Default, Hash, Component, Serialize, Deserialize)]
 pub struct SpriteSheetAnimation {
+    pub state: AnimationState,
+    /// Frame of the clip being shown.
+    pub frame: u8,
+    /// Simulation frames the current frame has been shown for.
     pub dt: u8,
-    pub speed: u8,
+    /// Set once a one-shot clip reached its last frame.
+    pub finished: bool,
+    pub facing_left: bool,
+}
+
+impl SpriteSheetAnimation {
+    /// Starts the clip of `state` from its first frame, unless it is already playing.
+    pub fn play(&mut self, state: AnimationState) {
+        if self.state != state {
+            *self = Self {
+                state,
+                facing_left: self.facing_left,
+                ..default()
+            };
+        }
+    }
+
+    pub fn atlas_index(&self) -> usize {
+        self.state.clip().first + self.frame as usize
+    }
+}
+
+impl_reflect_value!(SpriteSheetAnimation(Hash, Serialize, Deserialize));
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct AnimationEvent2D {
+    pub entity: Entity,
+    /// Rollback id of the entity, unlike the entity it is the same on every peer.
+    pub rollback_id: u32,
+    pub event: AnimationEvent,
 }
+
+impl Hash for AnimationEvent2D {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (self.rollback_id, self.event).hash(state);
+    }
+}
+
+/// Animation events of the last simulated frame, read by the gameplay systems of the next frame.
+#[derive(Clone, Default, Hash, Deref, DerefMut, Resource, Serialize, Deserialize)]
+pub struct AnimationEventsRes(pub Vec<AnimationEvent2D>);
+
+impl MapEntities for AnimationEventsRes {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        for animation_event in self.0.iter_mut() {
+            animation_event.entity = entity_map.get(animation_event.entity)?;
+        }
+        Ok(())
+    }
+}
+
+impl_reflect_value!(AnimationEventsRes(Hash, Serialize, Deserialize));
@@ -1,28 +1,135 @@
 use bevy::prelude::*;
 use bevy_ggrs::Rollback;
 
-use crate::game::core::anim::structs::SpriteSheetAnimation;
+use crate::game::core::anim::structs::{
+    AnimationEvent2D, AnimationEventsRes, SpriteSheetAnimation,
+};
+use crate::game::core::anim::utilities::speed_as_secs;
+use crate::game::core::settings::structs::GameSettings;
 
+/// Advances the clips and fires their events, independently of the textures so headless sessions animate too.
 pub fn animate_sprite_system(
+    settings: Res<GameSettings>,
+    //
+    mut animation_events: ResMut<AnimationEventsRes>,
+    mut query: Query<(Entity, &Rollback, &mut SpriteSheetAnimation)>,
+) {
+    animation_events.clear();
+
+    // events are ordered by rollback id, not by the random entity order
+    let mut animations: Vec<_> = query.iter_mut().collect();
+    animations.sort_unstable_by_key(|(_, rollback, _)| rollback.id());
+
+    for (entity, rollback, mut animation) in animations {
+        if animation.finished {
+            continue;
+        }
+        let clip = animation.state.clip();
+
+        if animation.dt == 0 {
+            for &(frame, event) in clip.events.iter() {
+                if frame == animation.frame {
+                    animation_events.push(AnimationEvent2D {
+                        entity,
+                        rollback_id: rollback.id(),
+                        event,
+                    });
+                }
+            }
+        }
+
+        animation.dt += 1;
+        if animation.dt >= speed_as_secs(settings.fps as u32, clip.frame_secs).max(1) {
+            animation.dt = 0;
+            if animation.frame + 1 < clip.len {
+                animation.frame += 1;
+            } else if clip.looping {
+                animation.frame = 0;
+            } else {
+                animation.finished = true;
+            }
+        }
+    }
+}
+
+/// Shows the current frame of the animations, outside of the rollback schedule.
+pub fn sprite_animation_system(
     mut query: Query<
-        (
-            &Handle<TextureAtlas>,
-            &mut TextureAtlasSprite,
-            &mut SpriteSheetAnimation,
-        ),
-        With<Rollback>,
+        (&SpriteSheetAnimation, &mut TextureAtlasSprite),
+        Changed<SpriteSheetAnimation>,
     >,
-    textures: Res<Assets<TextureAtlas>>,
 ) {
-    for (texture_atlas, mut sprite, mut sprite_sheet_animation) in query.iter_mut() {
-        if let Some(texture_atlas) = textures.get(texture_atlas) {
-            let nb_frames = texture_atlas.textures.len();
+    for (animation, mut sprite) in query.iter_mut() {
+        sprite.index = animation.atlas_index();
+        sprite.flip_x = animation.facing_left;
+    }
+}
 
-            if sprite_sheet_animation.dt > sprite_sheet_animation.speed {
-                sprite.index = (sprite.index + 1) % nb_frames;
-                sprite_sheet_animation.dt = 0;
-            }
-            sprite_sheet_animation.dt += 1;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ggrs::RollbackIdProvider;
+
+    use crate::game::core::anim::structs::{AnimationEvent, AnimationState};
+
+    fn animation_world(state: AnimationState) -> (World, Entity) {
+        let mut world = World::new();
+        let mut rollback_id_provider = RollbackIdProvider::default();
+        world.insert_resource(GameSettings::default());
+        world.insert_resource(AnimationEventsRes::default());
+        let mut animation = SpriteSheetAnimation::default();
+        animation.play(state);
+        let entity = world.spawn((animation, rollback_id_provider.next())).id();
+
+        (world, entity)
+    }
+
+    fn fired_events(world: &World) -> Vec<AnimationEvent> {
+        world
+            .resource::<AnimationEventsRes>()
+            .iter()
+            .map(|event| event.event)
+            .collect()
+    }
+
+    #[test]
+    fn events_fire_when_their_frame_is_shown() {
+        let (mut world, entity) = animation_world(AnimationState::Run);
+        let mut stage = SystemStage::single_threaded().with_system(animate_sprite_system);
+
+        // the first footstep is on the second frame, each frame is shown for 1/15 s, 4 frames at 60 fps
+        for _ in 0..4 {
+            stage.run(&mut world);
+            assert!(fired_events(&world).is_empty());
         }
+        stage.run(&mut world);
+
+        assert_eq!(fired_events(&world), vec![AnimationEvent::Footstep]);
+        assert_eq!(world.resource::<AnimationEventsRes>()[0].entity, entity);
+        assert_eq!(world.get::<SpriteSheetAnimation>(entity).unwrap().frame, 1);
+    }
+
+    #[test]
+    fn one_shot_clips_stop_on_their_last_frame() {
+        let (mut world, entity) = animation_world(AnimationState::Land);
+        let mut stage = SystemStage::single_threaded().with_system(animate_sprite_system);
+
+        stage.run(&mut world);
+        assert_eq!(fired_events(&world), vec![AnimationEvent::Land]);
+
+        // 2 frames shown for 1/20 s, 3 frames at 60 fps
+        for _ in 1..6 {
+            assert!(!world.get::<SpriteSheetAnimation>(entity).unwrap().finished);
+            stage.run(&mut world);
+        }
+
+        let animation = world.get::<SpriteSheetAnimation>(entity).unwrap();
+        assert!(animation.finished);
+        assert_eq!(animation.frame, 1);
+
+        // a finished clip neither advances nor fires again
+        stage.run(&mut world);
+        assert!(fired_events(&world).is_empty());
+        assert_eq!(world.get::<SpriteSheetAnimation>(entity).unwrap().frame, 1);
     }
 }
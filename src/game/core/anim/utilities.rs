@@ -1,3 +1,19 @@
-pub fn speed_as_secs(fps: u32, secs: f32) -> u8 {
-    return (secs * fps as f32) as u8;
+use crate::game::core::maths::structs::Fixed;
+
+/// Number of simulation frames closest to `secs`, tuning ratios such as 1/10 are a hair under their value.
+pub fn speed_as_secs(fps: u32, secs: Fixed) -> u8 {
+    (secs * Fixed::from_int(fps as i32)).round().to_i32() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn speed_as_secs_rounds_to_the_nearest_frame() {
+        assert_eq!(speed_as_secs(60, Fixed::from_ratio(1, 10)), 6);
+        assert_eq!(speed_as_secs(60, Fixed::from_ratio(1, 15)), 4);
+        assert_eq!(speed_as_secs(60, Fixed::from_ratio(1, 20)), 3);
+        assert_eq!(speed_as_secs(30, Fixed::from_ratio(1, 10)), 3);
+    }
 }
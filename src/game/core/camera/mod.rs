@@ -0,0 +1,3 @@
+pub mod structs;
+pub mod systems;
+pub mod utilities;
@@ -0,0 +1,88 @@
+use bevy::math::Rect;
+use bevy::prelude::*;
+use ggrs::PlayerHandle;
+use serde::{Deserialize, Serialize};
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Rate at which the cameras catch up with their target, per second.
+pub const CAMERA_FOLLOW_RATE: f32 = 6.0;
+/// Space kept around the frogs when fitting them all in view, in pixels.
+pub const CAMERA_FIT_MARGIN: f32 = 120.0;
+/// Bounds of the projection scale, 1 shows the world pixel for pixel.
+pub const CAMERA_MIN_ZOOM: f32 = 1.0;
+pub const CAMERA_MAX_ZOOM: f32 = 4.0;
+
+/// How the local players are framed, F7 cycles through the modes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CameraMode {
+    /// A single camera follows the first local player.
+    #[default]
+    Follow,
+    /// A single camera zooms out to keep every player in view.
+    FitAll,
+    /// One viewport per local player, a single camera when there is only one.
+    Split,
+}
+
+impl CameraMode {
+    pub fn next(self) -> Self {
+        match self {
+            CameraMode::Follow => CameraMode::FitAll,
+            CameraMode::FitAll => CameraMode::Split,
+            CameraMode::Split => CameraMode::Follow,
+        }
+    }
+}
+
+impl FromStr for CameraMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "follow" => Ok(CameraMode::Follow),
+            "fit-all" => Ok(CameraMode::FitAll),
+            "split" => Ok(CameraMode::Split),
+            _ => Err(format!(
+                "unknown camera mode {}, expected follow, fit-all or split",
+                s
+            )),
+        }
+    }
+}
+
+impl Display for CameraMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CameraMode::Follow => write!(f, "follow"),
+            CameraMode::FitAll => write!(f, "fit-all"),
+            CameraMode::Split => write!(f, "split"),
+        }
+    }
+}
+
+/// Non rollback resource, the cameras only show the simulation and never take part in it.
+#[derive(Resource)]
+pub struct CameraRes {
+    pub mode: CameraMode,
+    /// Area the cameras never look outside of, in pixels.
+    pub bounds: Option<Rect>,
+}
+
+impl CameraRes {
+    pub fn new(mode: CameraMode) -> Self {
+        Self { mode, bounds: None }
+    }
+}
+
+#[derive(Component)]
+pub struct GameCamera2D {
+    /// Player followed by the camera, every player when missing.
+    pub target: Option<PlayerHandle>,
+    /// Viewport of the camera among the viewports of a split screen.
+    pub viewport_index: usize,
+    pub viewport_count: usize,
+    /// Jumps to the target instead of moving smoothly, set until the target is found.
+    pub snap: bool,
+}
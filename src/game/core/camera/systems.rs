@@ -0,0 +1,178 @@
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::math::Rect;
+use bevy::prelude::*;
+use bevy::render::camera::Viewport;
+use bevy_ggrs::Session;
+
+use crate::game::core::camera::structs::*;
+use crate::game::core::camera::utilities::{clamp_to_bounds, split_viewport, watched_handles};
+use crate::game::core::level::structs::LevelRes;
+use crate::game::core::physics::range::scale_physics;
+use crate::game::player::structs::Player2D;
+use crate::game::GameConfig;
+
+pub fn startup_camera_system(level: Res<LevelRes>, mut camera: ResMut<CameraRes>) {
+    let ((min_x, min_y), (max_x, max_y)) = level.bounds();
+
+    camera.bounds = Some(Rect::new(
+        scale_physics(min_x).to_f32(),
+        scale_physics(min_y).to_f32(),
+        scale_physics(max_x).to_f32(),
+        scale_physics(max_y).to_f32(),
+    ));
+}
+
+/// Spawns the cameras of the current mode, again whenever the mode changes.
+pub fn camera_spawn_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    session: Res<Session<GameConfig>>,
+    //
+    mut commands: Commands,
+    mut camera: ResMut<CameraRes>,
+    //
+    query: Query<Entity, With<GameCamera2D>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F7) {
+        camera.mode = camera.mode.next();
+        info!("camera mode: {}", camera.mode);
+    }
+    if !camera.is_changed() {
+        return;
+    }
+
+    for entity in query.iter() {
+        commands.entity(entity).despawn();
+    }
+
+    let handles = watched_handles(&session);
+    let targets: Vec<_> = match camera.mode {
+        CameraMode::Follow => vec![handles.first().copied()],
+        CameraMode::FitAll => vec![None],
+        CameraMode::Split if handles.len() > 1 => handles.into_iter().map(Some).collect(),
+        CameraMode::Split => vec![handles.first().copied()],
+    };
+
+    let viewport_count = targets.len();
+    for (viewport_index, target) in targets.into_iter().enumerate() {
+        let mut entity = commands.spawn((
+            Camera2dBundle {
+                camera: Camera {
+                    priority: viewport_index as isize,
+                    ..default()
+                },
+                // clearing ignores the viewport, only the first camera clears the window
+                camera_2d: Camera2d {
+                    clear_color: if viewport_index == 0 {
+                        ClearColorConfig::Default
+                    } else {
+                        ClearColorConfig::None
+                    },
+                },
+                //
+                ..default()
+            },
+            GameCamera2D {
+                target,
+                viewport_index,
+                viewport_count,
+                snap: true,
+            },
+        ));
+
+        // the overlays are drawn once, over the whole window
+        if viewport_index > 0 {
+            entity.insert(UiCameraConfig { show_ui: false });
+        }
+    }
+}
+
+pub fn camera_system(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    camera: Res<CameraRes>,
+    //
+    mut query_camera: Query<(
+        &mut Camera,
+        &mut Transform,
+        &mut OrthographicProjection,
+        &mut GameCamera2D,
+    )>,
+    query_player: Query<(&Player2D, &Transform), Without<GameCamera2D>>,
+) {
+    let window = match windows.get_primary() {
+        Some(window) => window,
+        None => return,
+    };
+    let window_size = UVec2::new(window.physical_width(), window.physical_height());
+    let scale_factor = window.scale_factor() as f32;
+    let smoothing = 1.0 - (-CAMERA_FOLLOW_RATE * time.delta_seconds()).exp();
+
+    for (mut bevy_camera, mut transform, mut projection, mut game_camera) in query_camera.iter_mut()
+    {
+        let viewport = split_viewport(
+            window_size,
+            game_camera.viewport_index,
+            game_camera.viewport_count,
+        );
+        // viewports are not comparable, only their rectangles are
+        let rect = |viewport: &Option<Viewport>| {
+            viewport
+                .as_ref()
+                .map(|viewport| (viewport.physical_position, viewport.physical_size))
+        };
+        if rect(&bevy_camera.viewport) != rect(&viewport) {
+            bevy_camera.viewport = viewport;
+        }
+        let view_size = bevy_camera
+            .viewport
+            .as_ref()
+            .map_or(window_size, |viewport| viewport.physical_size)
+            .as_vec2()
+            / scale_factor;
+
+        // box around the targets of the camera
+        let mut targets = query_player
+            .iter()
+            .filter(|(player, _)| {
+                game_camera
+                    .target
+                    .is_none_or(|handle| handle == player.handle)
+            })
+            .map(|(_, transform)| transform.translation.truncate());
+        let first = match targets.next() {
+            Some(first) => first,
+            None => continue,
+        };
+        let (min, max) = targets.fold((first, first), |(min, max), position| {
+            (min.min(position), max.max(position))
+        });
+
+        let zoom = match game_camera.target {
+            Some(_) => CAMERA_MIN_ZOOM,
+            None => {
+                let fit = (max - min + 2.0 * CAMERA_FIT_MARGIN) / view_size;
+                fit.max_element().clamp(CAMERA_MIN_ZOOM, CAMERA_MAX_ZOOM)
+            }
+        };
+        let zoom = if game_camera.snap {
+            zoom
+        } else {
+            projection.scale + (zoom - projection.scale) * smoothing
+        };
+
+        let mut center = (min + max) / 2.0;
+        if let Some(bounds) = camera.bounds {
+            center = clamp_to_bounds(center, view_size * zoom / 2.0, bounds);
+        }
+        if !game_camera.snap {
+            center = transform.translation.truncate().lerp(center, smoothing);
+        }
+
+        if projection.scale != zoom {
+            projection.scale = zoom;
+        }
+        transform.translation.x = center.x;
+        transform.translation.y = center.y;
+        game_camera.snap = false;
+    }
+}
@@ -0,0 +1,120 @@
+use bevy::math::Rect;
+use bevy::prelude::*;
+use bevy::render::camera::Viewport;
+use bevy_ggrs::Session;
+use ggrs::PlayerHandle;
+
+use crate::game::core::input::utilities::local_player_index;
+use crate::game::{num_players, GameConfig};
+
+/// Players shown on this machine, the local ones in input order, every player for a spectator.
+pub fn watched_handles(session: &Session<GameConfig>) -> Vec<PlayerHandle> {
+    let handles = 0..num_players(session);
+
+    match session {
+        Session::SpectatorSession(_) => handles.collect(),
+        session => {
+            let mut local_handles: Vec<_> = handles
+                .filter_map(|handle| {
+                    local_player_index(session, handle).map(|index| (index, handle))
+                })
+                .collect();
+            local_handles.sort_unstable();
+            local_handles
+                .into_iter()
+                .map(|(_, handle)| handle)
+                .collect()
+        }
+    }
+}
+
+/// Viewport of a camera in a grid of two columns, a single viewport takes the whole window.
+pub fn split_viewport(window_size: UVec2, index: usize, count: usize) -> Option<Viewport> {
+    if count <= 1 {
+        return None;
+    }
+
+    let columns = 2;
+    let rows = (count as u32).div_ceil(columns);
+    let size = UVec2::new(window_size.x / columns, window_size.y / rows);
+    if size.x == 0 || size.y == 0 {
+        return None;
+    }
+
+    // the first players get the top of the window
+    let column = index as u32 % columns;
+    let row = index as u32 / columns;
+
+    Some(Viewport {
+        physical_position: UVec2::new(column * size.x, row * size.y),
+        physical_size: size,
+        ..default()
+    })
+}
+
+/// Center of the view, kept inside the bounds unless the view is larger than them.
+pub fn clamp_to_bounds(center: Vec2, half_extents: Vec2, bounds: Rect) -> Vec2 {
+    let clamp_axis = |center: f32, half_extent: f32, min: f32, max: f32| {
+        if max - min <= 2.0 * half_extent {
+            (min + max) / 2.0
+        } else {
+            center.clamp(min + half_extent, max - half_extent)
+        }
+    };
+
+    Vec2::new(
+        clamp_axis(center.x, half_extents.x, bounds.min.x, bounds.max.x),
+        clamp_axis(center.y, half_extents.y, bounds.min.y, bounds.max.y),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_viewport_lays_out_two_columns() {
+        let window_size = UVec2::new(800, 600);
+
+        assert!(split_viewport(window_size, 0, 1).is_none());
+
+        let viewport = split_viewport(window_size, 1, 2).unwrap();
+        assert_eq!(viewport.physical_position, UVec2::new(400, 0));
+        assert_eq!(viewport.physical_size, UVec2::new(400, 600));
+
+        let viewport = split_viewport(window_size, 2, 3).unwrap();
+        assert_eq!(viewport.physical_position, UVec2::new(0, 300));
+        assert_eq!(viewport.physical_size, UVec2::new(400, 300));
+    }
+
+    #[test]
+    fn split_viewport_skips_windows_too_small_to_split() {
+        assert!(split_viewport(UVec2::new(1, 600), 0, 2).is_none());
+        assert!(split_viewport(UVec2::new(0, 0), 0, 4).is_none());
+    }
+
+    #[test]
+    fn clamp_to_bounds_keeps_the_view_inside() {
+        let bounds = Rect::new(-20.0, -10.0, 20.0, 10.0);
+        let half_extents = Vec2::new(5.0, 5.0);
+
+        assert_eq!(
+            clamp_to_bounds(Vec2::new(1.0, 2.0), half_extents, bounds),
+            Vec2::new(1.0, 2.0)
+        );
+        assert_eq!(
+            clamp_to_bounds(Vec2::new(30.0, -30.0), half_extents, bounds),
+            Vec2::new(15.0, -5.0)
+        );
+    }
+
+    #[test]
+    fn clamp_to_bounds_centers_views_larger_than_the_bounds() {
+        let bounds = Rect::new(-20.0, 0.0, 20.0, 10.0);
+
+        assert_eq!(
+            clamp_to_bounds(Vec2::new(8.0, 8.0), Vec2::new(5.0, 6.0), bounds),
+            Vec2::new(8.0, 5.0)
+        );
+    }
+}
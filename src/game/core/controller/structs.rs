@@ -0,0 +1,42 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::game::core::maths::structs::{Fixed, Vector2D};
+
+// Character controller tuning, distances in meters and durations in seconds
+
+pub const MAX_RUN_SPEED: Fixed = Fixed::from_int(8);
+pub const RUN_ACCELERATION: Fixed = Fixed::from_int(60);
+pub const AIR_ACCELERATION: Fixed = Fixed::from_int(30);
+pub const GROUND_FRICTION: Fixed = Fixed::from_int(50);
+pub const AIR_FRICTION: Fixed = Fixed::from_int(5);
+pub const JUMP_SPEED: Fixed = Fixed::from_int(12);
+/// Share of the upward speed kept when the jump button is released early.
+pub const JUMP_CUT_FACTOR: Fixed = Fixed::from_ratio(1, 2);
+pub const CHARACTER_GRAVITY: Fixed = Fixed::from_int(30);
+pub const MAX_FALL_SPEED: Fixed = Fixed::from_int(20);
+/// Frames after walking off a ledge during which jumping is still allowed.
+pub const COYOTE_FRAMES: u8 = 6;
+/// Frames a jump pressed before landing is remembered.
+pub const JUMP_BUFFER_FRAMES: u8 = 6;
+
+// Shape cast tolerances, compared with the float geometry of rapier
+
+/// Cosine of the steepest walkable slope, 50 degrees.
+pub const MIN_GROUND_NORMAL_Y: f32 = 0.64;
+/// Gap kept between the character and the obstacles so casts never start in contact.
+pub const CHARACTER_SKIN: f32 = 0.01;
+/// Distance the character is pulled down to stay on descending slopes.
+pub const GROUND_SNAP_DISTANCE: f32 = 0.2;
+pub const MAX_SLIDE_ITERATIONS: usize = 4;
+
+/// Kinematic platformer movement state, part of the rollback state.
+#[derive(Hash, Default, Reflect, Component, Serialize, Deserialize)]
+#[reflect(Hash)]
+pub struct CharacterController2D {
+    /// Meters per second.
+    pub velocity: Vector2D,
+    pub grounded: bool,
+    pub frames_since_grounded: u8,
+    pub jump_buffer_frames: u8,
+}
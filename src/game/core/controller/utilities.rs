@@ -0,0 +1,142 @@
+use rapier2d::prelude::*;
+
+use crate::game::core::controller::structs::*;
+use crate::game::core::physics::systems::ONE_WAY_PHYSICS_GROUP;
+
+/// Shape being moved by the character controller and what it is allowed to collide with.
+pub struct CharacterShape<'a> {
+    pub shape: &'a dyn Shape,
+    pub position: Isometry<Real>,
+    pub filter: QueryFilter<'a>,
+    /// Ignore the one-way platforms, to drop through them.
+    pub drop_through: bool,
+}
+
+pub struct CharacterMovement {
+    pub translation: Vector<Real>,
+    pub ground_normal: Option<Vector<Real>>,
+    pub hit_ceiling: bool,
+    pub hit_wall: bool,
+}
+
+/// Moves the character as far as possible along `motion`, sliding along the obstacles on the way.
+pub fn move_and_slide(
+    query_pipeline: &QueryPipeline,
+    bodies: &RigidBodySet,
+    colliders: &ColliderSet,
+    character: &CharacterShape,
+    motion: Vector<Real>,
+) -> CharacterMovement {
+    let mut movement = CharacterMovement {
+        translation: Vector::zeros(),
+        ground_normal: None,
+        hit_ceiling: false,
+        hit_wall: false,
+    };
+
+    let mut remaining = motion;
+    for _ in 0..MAX_SLIDE_ITERATIONS {
+        let hit = cast_character(
+            query_pipeline,
+            bodies,
+            colliders,
+            character,
+            &movement.translation,
+            &remaining,
+        );
+
+        match hit {
+            None => {
+                movement.translation += remaining;
+                break;
+            }
+            Some((distance, normal)) => {
+                let direction = remaining.normalize();
+                let allowed = (distance - CHARACTER_SKIN).max(0.0);
+                movement.translation += direction * allowed;
+
+                if normal.y >= MIN_GROUND_NORMAL_Y {
+                    movement.ground_normal = Some(normal);
+                } else if normal.y <= -MIN_GROUND_NORMAL_Y {
+                    movement.hit_ceiling = true;
+                } else {
+                    movement.hit_wall = true;
+                }
+
+                // keep the part of the motion parallel to the obstacle
+                remaining -= direction * allowed;
+                remaining -= normal * remaining.dot(&normal);
+            }
+        }
+    }
+
+    movement
+}
+
+/// Looks for walkable ground right below the character, returns its distance and normal.
+pub fn probe_ground(
+    query_pipeline: &QueryPipeline,
+    bodies: &RigidBodySet,
+    colliders: &ColliderSet,
+    character: &CharacterShape,
+    offset: &Vector<Real>,
+    max_distance: Real,
+) -> Option<(Real, Vector<Real>)> {
+    cast_character(
+        query_pipeline,
+        bodies,
+        colliders,
+        character,
+        offset,
+        &vector![0.0, -max_distance],
+    )
+    .filter(|(_, normal)| normal.y >= MIN_GROUND_NORMAL_Y)
+}
+
+/// Casts the character shape from `offset` along `motion`, returns the distance and the normal of the first obstacle.
+fn cast_character(
+    query_pipeline: &QueryPipeline,
+    bodies: &RigidBodySet,
+    colliders: &ColliderSet,
+    character: &CharacterShape,
+    offset: &Vector<Real>,
+    motion: &Vector<Real>,
+) -> Option<(Real, Vector<Real>)> {
+    let length = motion.norm();
+    if length <= Real::EPSILON {
+        return None;
+    }
+    let direction = motion / length;
+
+    let position = Isometry::translation(
+        character.position.translation.x + offset.x,
+        character.position.translation.y + offset.y,
+    );
+    let bottom = position.translation.y - character.shape.compute_local_aabb().half_extents().y;
+
+    // one-way platforms only stop a character falling onto them from above
+    let one_way_predicate = |_: ColliderHandle, collider: &Collider| {
+        !collider
+            .collision_groups()
+            .memberships
+            .contains(ONE_WAY_PHYSICS_GROUP)
+            || (!character.drop_through
+                && direction.y < 0.0
+                && bottom >= collider.compute_aabb().maxs.y - CHARACTER_SKIN)
+    };
+    let filter = character.filter.predicate(&one_way_predicate);
+
+    query_pipeline
+        .cast_shape(
+            bodies,
+            colliders,
+            &position,
+            &direction,
+            character.shape,
+            length,
+            true,
+            filter,
+        )
+        // the character never rotates, its local normal is a world normal
+        .map(|(_, toi)| (toi.toi, -toi.normal2.into_inner()))
+}
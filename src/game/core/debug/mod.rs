@@ -1,5 +1,7 @@
 use bevy::prelude::*;
 
+use crate::game::core::diagnostics::structs::NetworkDiagnosticsRes;
+
 pub fn debug_system(world: &mut World) {
     let mut all_entities = world.query::<Entity>();
 
@@ -8,8 +10,10 @@ pub fn debug_system(world: &mut World) {
     let archetypes = world.archetypes();
     let components = world.components();
 
+    let toggle_network_diagnostics = keyboard.just_pressed(KeyCode::F2);
+
     if keyboard.just_pressed(KeyCode::F1) {
-        for entity in all_entities.iter(&world) {
+        for entity in all_entities.iter(world) {
             println!("Entity: {:?}", entity);
             if let Some(parent) = world.entity(entity).get::<Parent>() {
                 println!("\tParent: {:?}", parent.get());
@@ -25,4 +29,10 @@ pub fn debug_system(world: &mut World) {
             }
         }
     }
+
+    if toggle_network_diagnostics {
+        if let Some(mut network_diagnostics) = world.get_resource_mut::<NetworkDiagnosticsRes>() {
+            network_diagnostics.visible = !network_diagnostics.visible;
+        }
+    }
 }
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use std::collections::VecDeque;
+
+/// Number of one second samples shown by the rolling graph.
+pub const NETWORK_DIAGNOSTICS_HISTORY: usize = 60;
+pub const NETWORK_DIAGNOSTICS_GRAPH_SIZE: Vec2 = Vec2::new(240.0, 60.0);
+/// Render layer of the graph, only seen by the overlay camera.
+pub const NETWORK_DIAGNOSTICS_LAYER: u8 = 1;
+/// Draws the overlay camera after every game camera, whatever the split screen viewports.
+pub const NETWORK_DIAGNOSTICS_CAMERA_PRIORITY: isize = 100;
+
+#[derive(Clone, Copy, Default)]
+pub struct RollbackSample {
+    pub rollbacks: u32,
+    pub resimulated_frames: u32,
+    pub max_depth: u32,
+}
+
+/// Non rollback resource counting the rollbacks seen by the rollback schedule,
+/// a rollback shows up as a frame simulated again after a later one.
+#[derive(Default, Resource)]
+pub struct NetworkDiagnosticsRes {
+    pub visible: bool,
+    pub last_frame: u32,
+    pub max_frame: u32,
+    pub current: RollbackSample,
+    pub history: VecDeque<RollbackSample>,
+    pub elapsed: f32,
+}
+
+impl NetworkDiagnosticsRes {
+    pub fn record_frame(&mut self, frame: u32) {
+        if frame <= self.last_frame {
+            self.current.rollbacks += 1;
+            self.current.max_depth = self.current.max_depth.max(self.max_frame - frame + 1);
+        }
+        if frame <= self.max_frame {
+            self.current.resimulated_frames += 1;
+        }
+
+        self.last_frame = frame;
+        self.max_frame = self.max_frame.max(frame);
+    }
+
+    pub fn push_sample(&mut self) {
+        self.history.push_back(std::mem::take(&mut self.current));
+        while self.history.len() > NETWORK_DIAGNOSTICS_HISTORY {
+            self.history.pop_front();
+        }
+    }
+}
+
+#[derive(Component)]
+pub struct NetworkDiagnosticsText;
+
+/// Screen space camera drawing the graph over the whole window.
+#[derive(Component)]
+pub struct NetworkDiagnosticsCamera;
+
+#[derive(Clone, Copy, Component)]
+pub enum NetworkDiagnosticsGraph {
+    Rollbacks,
+    ResimulatedFrames,
+}
+
+impl NetworkDiagnosticsGraph {
+    pub fn value(&self, sample: &RollbackSample) -> u32 {
+        match self {
+            NetworkDiagnosticsGraph::Rollbacks => sample.rollbacks,
+            NetworkDiagnosticsGraph::ResimulatedFrames => sample.resimulated_frames,
+        }
+    }
+}
@@ -0,0 +1,186 @@
+use bevy::core_pipeline::clear_color::ClearColorConfig;
+use bevy::prelude::*;
+use bevy::render::camera::WindowOrigin;
+use bevy::render::view::RenderLayers;
+use bevy_ggrs::Session;
+use bevy_prototype_lyon::prelude::*;
+use std::collections::VecDeque;
+use std::fmt::Write;
+
+use crate::game::core::diagnostics::structs::*;
+use crate::game::core::frame::structs::FrameCount;
+use crate::game::core::network::structs::NetworkStatusRes;
+use crate::game::GameConfig;
+
+pub fn rollback_diagnostics_system(
+    frame_count: Res<FrameCount>,
+    network_diagnostics: Option<ResMut<NetworkDiagnosticsRes>>,
+) {
+    if let Some(mut network_diagnostics) = network_diagnostics {
+        network_diagnostics.record_frame(frame_count.frame);
+    }
+}
+
+pub fn startup_network_diagnostics_system(
+    asset_server: Res<AssetServer>,
+    //
+    mut commands: Commands,
+) {
+    let font_handle: Handle<Font> = asset_server.load("fonts/Pixellari.ttf");
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_handle,
+                color: Color::WHITE,
+                font_size: 14.0,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(5.0),
+                left: Val::Px(5.0),
+                ..default()
+            },
+            ..default()
+        }),
+        NetworkDiagnosticsText,
+    ));
+
+    // the game cameras follow the players and zoom, the graph gets its own camera in window
+    // coordinates, drawn over them and anchored to the bottom left corner of the window
+    commands.spawn((
+        Camera2dBundle {
+            camera: Camera {
+                priority: NETWORK_DIAGNOSTICS_CAMERA_PRIORITY,
+                ..default()
+            },
+            camera_2d: Camera2d {
+                clear_color: ClearColorConfig::None,
+            },
+            projection: OrthographicProjection {
+                window_origin: WindowOrigin::BottomLeft,
+                ..default()
+            },
+            ..default()
+        },
+        UiCameraConfig { show_ui: false },
+        RenderLayers::layer(NETWORK_DIAGNOSTICS_LAYER),
+        NetworkDiagnosticsCamera,
+    ));
+
+    let origin = Vec3::new(5.0, 5.0, 0.0);
+    for (graph, color) in [
+        (NetworkDiagnosticsGraph::ResimulatedFrames, Color::RED),
+        (NetworkDiagnosticsGraph::Rollbacks, Color::YELLOW),
+    ] {
+        commands.spawn((
+            GeometryBuilder::build_as(
+                &graph_shape(graph, &Default::default()),
+                DrawMode::Stroke(StrokeMode::new(color, 1.0)),
+                Transform::from_translation(origin),
+            ),
+            RenderLayers::layer(NETWORK_DIAGNOSTICS_LAYER),
+            graph,
+        ));
+    }
+}
+
+#[allow(clippy::type_complexity)]
+pub fn network_diagnostics_system(
+    time: Res<Time>,
+    session: Res<Session<GameConfig>>,
+    network_status: Res<NetworkStatusRes>,
+    //
+    mut network_diagnostics: ResMut<NetworkDiagnosticsRes>,
+    mut query_visibility: Query<
+        &mut Visibility,
+        Or<(With<NetworkDiagnosticsText>, With<NetworkDiagnosticsGraph>)>,
+    >,
+    mut query_text: Query<&mut Text, With<NetworkDiagnosticsText>>,
+    mut query_graph: Query<(&mut Path, &NetworkDiagnosticsGraph)>,
+) {
+    for mut visibility in query_visibility.iter_mut() {
+        visibility.is_visible = network_diagnostics.visible;
+    }
+
+    network_diagnostics.elapsed += time.delta_seconds();
+    if network_diagnostics.elapsed < 1.0 {
+        return;
+    }
+    network_diagnostics.elapsed -= 1.0;
+    network_diagnostics.push_sample();
+
+    let sample = network_diagnostics
+        .history
+        .back()
+        .copied()
+        .unwrap_or_default();
+    let mut text = format!(
+        "rollbacks/s: {}\nresimulated frames/s: {}\nmax rollback depth: {}\nrecommended wait: {} frames\n",
+        sample.rollbacks,
+        sample.resimulated_frames,
+        sample.max_depth,
+        network_status.recommended_wait_frames
+    );
+    if let Session::P2PSession(s) = &*session {
+        for handle in s.remote_player_handles() {
+            match s.network_stats(handle) {
+                Ok(stats) => writeln!(
+                    text,
+                    "player {}: ping {} ms, send queue {}, frames ahead {}, {} kbps",
+                    handle,
+                    stats.ping,
+                    stats.send_queue_len,
+                    -stats.local_frames_behind,
+                    stats.kbps_sent
+                ),
+                Err(e) => writeln!(text, "player {}: {}", handle, e),
+            }
+            .unwrap();
+        }
+    }
+
+    for mut diagnostics_text in query_text.iter_mut() {
+        diagnostics_text.sections[0].value = text.clone();
+    }
+
+    for (mut path, graph) in query_graph.iter_mut() {
+        *path = ShapePath::build_as(&graph_shape(*graph, &network_diagnostics.history));
+    }
+}
+
+fn graph_shape(
+    graph: NetworkDiagnosticsGraph,
+    history: &VecDeque<RollbackSample>,
+) -> shapes::Polygon {
+    // every rollback resimulates at least one frame, both graphs share the resimulated frames scale
+    let max_value = history
+        .iter()
+        .map(|sample| sample.resimulated_frames)
+        .max()
+        .unwrap_or_default()
+        .max(1) as f32;
+    let step = NETWORK_DIAGNOSTICS_GRAPH_SIZE.x / (NETWORK_DIAGNOSTICS_HISTORY - 1) as f32;
+
+    let mut points: Vec<Vec2> = history
+        .iter()
+        .enumerate()
+        .map(|(i, sample)| {
+            Vec2::new(
+                i as f32 * step,
+                graph.value(sample) as f32 / max_value * NETWORK_DIAGNOSTICS_GRAPH_SIZE.y,
+            )
+        })
+        .collect();
+    if points.len() < 2 {
+        points = vec![Vec2::ZERO, Vec2::new(NETWORK_DIAGNOSTICS_GRAPH_SIZE.x, 0.0)];
+    }
+
+    shapes::Polygon {
+        points,
+        closed: false,
+    }
+}
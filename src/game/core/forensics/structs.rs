@@ -0,0 +1,360 @@
+use bevy::ecs::entity::{EntityMap, MapEntities};
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use log::warn;
+use ron::ser::PrettyConfig;
+use ron::Value;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::any::type_name;
+use std::collections::{BTreeMap, VecDeque};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Number of simulated frames kept in memory, must cover the GGRS desync detection interval.
+pub const SNAPSHOT_HISTORY_FRAMES: usize = 120;
+pub const SNAPSHOT_DUMP_DIRECTORY: &str = "desync";
+
+/// Sent when a desync is reported, either by the synctest checksums or by a remote peer.
+pub struct DesyncDetectedEvent {
+    pub frame: u32,
+}
+
+/// Serialized state of every rollback resource and component at the end of a simulated frame.
+pub struct FrameSnapshot {
+    pub frame: u32,
+    pub resources: Vec<Option<Vec<u8>>>,
+    pub components: Vec<Vec<(u32, Vec<u8>)>>,
+}
+
+/// Human readable version of a [`FrameSnapshot`], as written to disk.
+#[derive(Serialize, Deserialize)]
+pub struct FrameDump {
+    pub frame: u32,
+    pub resources: BTreeMap<String, Value>,
+    pub entities: BTreeMap<u32, BTreeMap<String, Value>>,
+}
+
+/// Serializes a resource, its entities replaced by their rollback ids with the given map.
+pub type SaveResourceFn = fn(&World, &EntityMap) -> Option<Vec<u8>>;
+/// Serializes every rollback entity holding a component, by rollback id.
+pub type SaveComponentsFn = fn(&mut World) -> Vec<(u32, Vec<u8>)>;
+/// Reads back a serialized resource or component for the dump.
+pub type LoadEntryFn = fn(&[u8]) -> Result<Value, Box<dyn Error>>;
+
+pub struct SnapshotResourceEntry {
+    pub name: &'static str,
+    pub save: SaveResourceFn,
+    pub load: LoadEntryFn,
+}
+
+pub struct SnapshotComponentEntry {
+    pub name: &'static str,
+    pub save: SaveComponentsFn,
+    pub load: LoadEntryFn,
+}
+
+/// Non rollback resource keeping the last simulated frames around so they can be dumped on desync.
+#[derive(Resource)]
+pub struct SnapshotHistoryRes {
+    pub capacity: usize,
+    pub frames: VecDeque<FrameSnapshot>,
+    pub resources: Vec<SnapshotResourceEntry>,
+    pub components: Vec<SnapshotComponentEntry>,
+    /// Snapshot of the resimulated frame which did not match its first simulation (synctest only).
+    pub diverged: Option<FrameSnapshot>,
+}
+
+impl SnapshotHistoryRes {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            frames: VecDeque::with_capacity(capacity),
+            resources: Vec::new(),
+            components: Vec::new(),
+            diverged: None,
+        }
+    }
+
+    pub fn with_resource<R: Resource + Serialize + DeserializeOwned>(mut self) -> Self {
+        self.resources.push(SnapshotResourceEntry {
+            name: short_type_name::<R>(),
+            save: save_resource::<R>,
+            load: load_value::<R>,
+        });
+        self
+    }
+
+    /// Registers a resource holding entities, they differ between peers so they are dumped as their rollback ids.
+    pub fn with_mapped_resource<
+        R: Resource + Clone + MapEntities + Serialize + DeserializeOwned,
+    >(
+        mut self,
+    ) -> Self {
+        self.resources.push(SnapshotResourceEntry {
+            name: short_type_name::<R>(),
+            save: save_mapped_resource::<R>,
+            load: load_value::<R>,
+        });
+        self
+    }
+
+    pub fn with_component<C: Component + Serialize + DeserializeOwned>(mut self) -> Self {
+        self.components.push(SnapshotComponentEntry {
+            name: short_type_name::<C>(),
+            save: save_component::<C>,
+            load: load_value::<C>,
+        });
+        self
+    }
+
+    pub fn is_frozen(&self) -> bool {
+        self.diverged.is_some()
+    }
+
+    pub fn take_snapshot(&self, frame: u32, world: &mut World) -> FrameSnapshot {
+        let rollback_entities = rollback_entity_map(world);
+
+        FrameSnapshot {
+            frame,
+            resources: self
+                .resources
+                .iter()
+                .map(|entry| (entry.save)(world, &rollback_entities))
+                .collect(),
+            components: self
+                .components
+                .iter()
+                .map(|entry| (entry.save)(world))
+                .collect(),
+        }
+    }
+
+    /// Records a frame snapshot, a resimulated frame replaces the previous simulation of the same frame.
+    pub fn record(&mut self, snapshot: FrameSnapshot) {
+        // rollbacks resimulate older frames, forget everything from the resimulated frame onwards
+        while matches!(self.frames.back(), Some(last) if last.frame >= snapshot.frame) {
+            self.frames.pop_back();
+        }
+        if self.frames.len() >= self.capacity {
+            self.frames.pop_front();
+        }
+        self.frames.push_back(snapshot);
+    }
+
+    /// Writes the recorded frames to the dump directory, returns the paths of the written files.
+    pub fn dump(&self, frame: u32, label: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        fs::create_dir_all(SNAPSHOT_DUMP_DIRECTORY)?;
+
+        let mut paths = Vec::new();
+        let path =
+            PathBuf::from(SNAPSHOT_DUMP_DIRECTORY).join(format!("{}_frame{}.ron", label, frame));
+        self.write_dump(&path, self.frames.iter())?;
+        paths.push(path);
+
+        if let Some(diverged) = &self.diverged {
+            let path = PathBuf::from(SNAPSHOT_DUMP_DIRECTORY)
+                .join(format!("{}_frame{}_resimulated.ron", label, frame));
+            self.write_dump(
+                &path,
+                self.frames
+                    .iter()
+                    .filter(|snapshot| snapshot.frame != diverged.frame)
+                    .chain(std::iter::once(diverged)),
+            )?;
+            paths.push(path);
+        }
+
+        Ok(paths)
+    }
+
+    fn write_dump<'a>(
+        &self,
+        path: &Path,
+        snapshots: impl Iterator<Item = &'a FrameSnapshot>,
+    ) -> Result<(), Box<dyn Error>> {
+        let mut dumps = snapshots
+            .map(|snapshot| self.frame_dump(snapshot))
+            .collect::<Result<Vec<_>, _>>()?;
+        dumps.sort_by_key(|dump| dump.frame);
+
+        fs::write(
+            path,
+            ron::ser::to_string_pretty(&dumps, PrettyConfig::default())?,
+        )?;
+
+        Ok(())
+    }
+
+    fn frame_dump(&self, snapshot: &FrameSnapshot) -> Result<FrameDump, Box<dyn Error>> {
+        let mut dump = FrameDump {
+            frame: snapshot.frame,
+            resources: BTreeMap::new(),
+            entities: BTreeMap::new(),
+        };
+        for (entry, bytes) in self.resources.iter().zip(&snapshot.resources) {
+            if let Some(bytes) = bytes {
+                dump.resources
+                    .insert(entry.name.to_owned(), (entry.load)(bytes)?);
+            }
+        }
+        for (entry, components) in self.components.iter().zip(&snapshot.components) {
+            for (rollback_id, bytes) in components {
+                dump.entities
+                    .entry(*rollback_id)
+                    .or_default()
+                    .insert(entry.name.to_owned(), (entry.load)(bytes)?);
+            }
+        }
+
+        Ok(dump)
+    }
+}
+
+fn short_type_name<T>() -> &'static str {
+    type_name::<T>().rsplit("::").next().unwrap()
+}
+
+/// Maps every rollback entity to an entity whose index is its rollback id, the same on every peer.
+fn rollback_entity_map(world: &mut World) -> EntityMap {
+    let mut rollback_entities = EntityMap::default();
+    let mut query = world.query::<(Entity, &Rollback)>();
+
+    for (entity, rollback) in query.iter(world) {
+        rollback_entities.insert(entity, Entity::from_raw(rollback.id()));
+    }
+    rollback_entities
+}
+
+fn save_resource<R: Resource + Serialize>(world: &World, _: &EntityMap) -> Option<Vec<u8>> {
+    world.get_resource::<R>().map(|resource| {
+        bincode::serialize(resource).expect("failed to serialize rollback resource")
+    })
+}
+
+fn save_mapped_resource<R: Resource + Clone + MapEntities + Serialize>(
+    world: &World,
+    rollback_entities: &EntityMap,
+) -> Option<Vec<u8>> {
+    let mut resource = world.get_resource::<R>()?.clone();
+    if let Err(e) = resource.map_entities(rollback_entities) {
+        warn!("{} not snapshotted: {}", short_type_name::<R>(), e);
+        return None;
+    }
+
+    Some(bincode::serialize(&resource).expect("failed to serialize rollback resource"))
+}
+
+fn save_component<C: Component + Serialize>(world: &mut World) -> Vec<(u32, Vec<u8>)> {
+    let mut query = world.query::<(&Rollback, &C)>();
+
+    query
+        .iter(world)
+        .map(|(rollback, component)| {
+            (
+                rollback.id(),
+                bincode::serialize(component).expect("failed to serialize rollback component"),
+            )
+        })
+        .collect()
+}
+
+fn load_value<T: Serialize + DeserializeOwned>(bytes: &[u8]) -> Result<Value, Box<dyn Error>> {
+    let value: T = bincode::deserialize(bytes)?;
+
+    Ok(ron::from_str(&ron::to_string(&value)?)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy_ggrs::RollbackIdProvider;
+    use rapier2d::prelude::{ColliderBuilder, ColliderSet, RigidBodyHandle};
+
+    use crate::game::core::anim::structs::{AnimationEvent, AnimationEvent2D, AnimationEventsRes};
+    use crate::game::core::maths::structs::{Transform2D, Vector2D};
+    use crate::game::core::physics::structs::{
+        ColliderSetRes, PhysicsEvent2D, PhysicsEventsRes, RigidBodyRemovedEntitiesRes,
+    };
+
+    /// World of a peer which spawned `unrelated_entities` entities before the rollback ones.
+    fn peer_world(unrelated_entities: usize) -> World {
+        let mut world = World::new();
+        let mut rollback_id_provider = RollbackIdProvider::default();
+        for _ in 0..unrelated_entities {
+            world.spawn_empty();
+        }
+
+        let frog = world
+            .spawn((
+                Transform2D::from_position(Vector2D::from_f32(1.0, 2.0)),
+                rollback_id_provider.next(),
+            ))
+            .id();
+        let ball = world
+            .spawn((
+                Transform2D::from_position(Vector2D::from_f32(3.0, 4.0)),
+                rollback_id_provider.next(),
+            ))
+            .id();
+        let (frog_body, ball_body) = (
+            RigidBodyHandle::from_raw_parts(0, 0),
+            RigidBodyHandle::from_raw_parts(1, 0),
+        );
+
+        world.insert_resource(RigidBodyRemovedEntitiesRes(
+            [(frog, frog_body), (ball, ball_body)].into_iter().collect(),
+        ));
+        world.insert_resource(PhysicsEventsRes(vec![PhysicsEvent2D::CollisionStarted {
+            entity1: frog,
+            entity2: ball,
+            rigid_bodies: (frog_body, ball_body),
+            sensor: false,
+        }]));
+        world.insert_resource(AnimationEventsRes(vec![AnimationEvent2D {
+            entity: frog,
+            rollback_id: 0,
+            event: AnimationEvent::Takeoff,
+        }]));
+
+        world
+    }
+
+    fn dump_of(world: &mut World) -> String {
+        let snapshot_history = SnapshotHistoryRes::new(1)
+            .with_mapped_resource::<RigidBodyRemovedEntitiesRes>()
+            .with_mapped_resource::<PhysicsEventsRes>()
+            .with_mapped_resource::<AnimationEventsRes>()
+            .with_component::<Transform2D>();
+        let snapshot = snapshot_history.take_snapshot(0, world);
+        let dump = snapshot_history.frame_dump(&snapshot).unwrap();
+
+        assert_eq!(dump.resources.len(), 3);
+        assert_eq!(dump.entities.len(), 2);
+        ron::to_string(&dump).unwrap()
+    }
+
+    #[test]
+    fn peers_with_other_entity_ids_dump_the_same_state() {
+        let mut world = peer_world(0);
+        let mut other_world = peer_world(5);
+
+        assert_eq!(dump_of(&mut world), dump_of(&mut other_world));
+    }
+
+    #[test]
+    fn collider_sets_are_dumped() {
+        let mut collider_set = ColliderSet::new();
+        collider_set.insert(ColliderBuilder::ball(1.0).user_data(u128::MAX).build());
+        let mut world = World::new();
+        world.insert_resource(ColliderSetRes(collider_set));
+
+        // the user data of the colliders is a u128
+        let snapshot_history = SnapshotHistoryRes::new(1).with_resource::<ColliderSetRes>();
+        let snapshot = snapshot_history.take_snapshot(0, &mut world);
+        let dump = snapshot_history.frame_dump(&snapshot).unwrap();
+
+        assert_eq!(dump.resources.len(), 1);
+    }
+}
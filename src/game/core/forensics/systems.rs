@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use log::error;
+
+use crate::game::core::forensics::structs::{DesyncDetectedEvent, SnapshotHistoryRes};
+use crate::game::core::frame::structs::FrameCount;
+use crate::game::core::synctest::structs::SyncTestChecksumsRes;
+use crate::game::GameConfig;
+
+pub fn snapshot_system(world: &mut World) {
+    world.resource_scope(|world, mut snapshot_history: Mut<SnapshotHistoryRes>| {
+        if snapshot_history.is_frozen() {
+            return;
+        }
+
+        let frame = world.resource::<FrameCount>().frame;
+        let snapshot = snapshot_history.take_snapshot(frame, world);
+        let mismatch = world
+            .get_resource::<SyncTestChecksumsRes>()
+            .and_then(|synctest_checksums| synctest_checksums.mismatch.clone());
+
+        match mismatch {
+            // keep both simulations of the mismatching frame and stop recording
+            Some(mismatch) => {
+                snapshot_history.diverged = Some(snapshot);
+                world.send_event(DesyncDetectedEvent {
+                    frame: mismatch.frame,
+                });
+            }
+            None => snapshot_history.record(snapshot),
+        }
+    });
+}
+
+pub fn desync_dump_system(
+    session: Res<Session<GameConfig>>,
+    snapshot_history: Res<SnapshotHistoryRes>,
+    mut desync_detected_events: EventReader<DesyncDetectedEvent>,
+) {
+    for desync_detected_event in desync_detected_events.iter() {
+        let label = match &*session {
+            Session::P2PSession(s) => format!(
+                "player{}",
+                s.local_player_handles()
+                    .iter()
+                    .map(|handle| handle.to_string())
+                    .collect::<Vec<_>>()
+                    .join("_")
+            ),
+            Session::SyncTestSession(_) => "synctest".to_owned(),
+            Session::SpectatorSession(_) => "spectator".to_owned(),
+        };
+
+        match snapshot_history.dump(desync_detected_event.frame, &label) {
+            Ok(paths) => error!(
+                "desync detected at frame {}, snapshots written to {:?}",
+                desync_detected_event.frame, paths
+            ),
+            Err(e) => error!(
+                "desync detected at frame {}, failed to write snapshots: {}",
+                desync_detected_event.frame, e
+            ),
+        }
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Hash, Default, Reflect, Resource)]
+#[derive(Hash, Default, Reflect, Resource, Serialize, Deserialize)]
 #[reflect(Hash)]
 pub struct FrameCount {
     pub frame: u32,
@@ -1,2 +1,3 @@
 pub mod structs;
 pub mod systems;
+pub mod utilities;
@@ -1,13 +1,678 @@
+use bevy::prelude::*;
 use bytemuck::{Pod, Zeroable};
+use derive_more::{Deref, DerefMut};
+use ggrs::{InputStatus, PlayerHandle};
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
 
-pub const INPUT_UP: u8 = 1 << 1;
-pub const INPUT_DOWN: u8 = 1 << 2;
-pub const INPUT_LEFT: u8 = 1 << 3;
-pub const INPUT_RIGHT: u8 = 1 << 4;
-pub const INPUT_JUMP: u8 = 1 << 5;
+use crate::game::core::maths::structs::Fixed;
+
+pub const INPUT_UP: u16 = 1 << 1;
+pub const INPUT_DOWN: u16 = 1 << 2;
+pub const INPUT_LEFT: u16 = 1 << 3;
+pub const INPUT_RIGHT: u16 = 1 << 4;
+pub const INPUT_JUMP: u16 = 1 << 5;
+pub const INPUT_ATTACK: u16 = 1 << 6;
+pub const INPUT_GRAB: u16 = 1 << 7;
+pub const INPUT_TONGUE: u16 = 1 << 8;
+pub const INPUT_ALL: u16 = INPUT_UP
+    | INPUT_DOWN
+    | INPUT_LEFT
+    | INPUT_RIGHT
+    | INPUT_JUMP
+    | INPUT_ATTACK
+    | INPUT_GRAB
+    | INPUT_TONGUE;
+
+/// Value of a fully deflected axis, axes are quantized so they are deterministic and small on the wire.
+pub const INPUT_AXIS_MAX: i8 = 127;
 
 #[repr(C)]
 #[derive(Copy, Clone, PartialEq, Eq, Pod, Zeroable)]
 pub struct BoxInput {
-    pub inp: u8,
+    pub inp: u16,
+    pub axis_x: i8,
+    pub axis_y: i8,
+}
+
+impl BoxInput {
+    /// Digital input, directions fully deflect the axes.
+    pub fn from_buttons(inp: u16) -> Self {
+        let axis = |negative, positive| match (inp & negative != 0, inp & positive != 0) {
+            (true, false) => -INPUT_AXIS_MAX,
+            (false, true) => INPUT_AXIS_MAX,
+            _ => 0,
+        };
+
+        Self {
+            inp,
+            axis_x: axis(INPUT_LEFT, INPUT_RIGHT),
+            axis_y: axis(INPUT_DOWN, INPUT_UP),
+        }
+    }
+
+    /// Quantizes stick values between -1 and 1.
+    pub fn quantize_axis(value: f32) -> i8 {
+        (value.clamp(-1.0, 1.0) * INPUT_AXIS_MAX as f32).round() as i8
+    }
+
+    /// Combines two input devices, the most deflected axes win.
+    pub fn merge(self, other: BoxInput) -> BoxInput {
+        let axis = |a: i8, b: i8| {
+            if a.unsigned_abs() >= b.unsigned_abs() {
+                a
+            } else {
+                b
+            }
+        };
+
+        Self {
+            inp: self.inp | other.inp,
+            axis_x: axis(self.axis_x, other.axis_x),
+            axis_y: axis(self.axis_y, other.axis_y),
+        }
+    }
+
+    pub fn pressed(&self, button: u16) -> bool {
+        self.inp & button != 0
+    }
+
+    /// Horizontal axis between -1 and 1.
+    pub fn axis_x(&self) -> Fixed {
+        Fixed::from_ratio(self.axis_x as i32, INPUT_AXIS_MAX as i32)
+    }
+
+    #[allow(dead_code)]
+    pub fn axis_y(&self) -> Fixed {
+        Fixed::from_ratio(self.axis_y as i32, INPUT_AXIS_MAX as i32)
+    }
+}
+
+/// Input of a player during the previous frame, rolled back with the player so edges stay consistent.
+#[derive(Hash, Default, Reflect, Component, Serialize, Deserialize)]
+#[reflect(Hash)]
+pub struct PreviousInput2D {
+    pub inp: u16,
+}
+
+impl PreviousInput2D {
+    pub fn just_pressed(&self, input: BoxInput, button: u16) -> bool {
+        input.inp & !self.inp & button != 0
+    }
+
+    pub fn just_released(&self, input: BoxInput, button: u16) -> bool {
+        !input.inp & self.inp & button != 0
+    }
+}
+
+pub const INPUT_BINDINGS_DIRECTORY: &str = "controls";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InputAction {
+    Up,
+    Down,
+    Left,
+    Right,
+    Jump,
+    Attack,
+    Grab,
+    Tongue,
+}
+
+impl InputAction {
+    pub const ALL: [InputAction; 8] = [
+        InputAction::Up,
+        InputAction::Down,
+        InputAction::Left,
+        InputAction::Right,
+        InputAction::Jump,
+        InputAction::Attack,
+        InputAction::Grab,
+        InputAction::Tongue,
+    ];
+
+    pub fn input(&self) -> u16 {
+        match self {
+            InputAction::Up => INPUT_UP,
+            InputAction::Down => INPUT_DOWN,
+            InputAction::Left => INPUT_LEFT,
+            InputAction::Right => INPUT_RIGHT,
+            InputAction::Jump => INPUT_JUMP,
+            InputAction::Attack => INPUT_ATTACK,
+            InputAction::Grab => INPUT_GRAB,
+            InputAction::Tongue => INPUT_TONGUE,
+        }
+    }
+}
+
+/// A key or a button for every action.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActionBindings<T> {
+    pub up: T,
+    pub down: T,
+    pub left: T,
+    pub right: T,
+    pub jump: T,
+    pub attack: T,
+    pub grab: T,
+    pub tongue: T,
+}
+
+impl<T: Copy + PartialEq> ActionBindings<T> {
+    pub fn get(&self, action: InputAction) -> T {
+        match action {
+            InputAction::Up => self.up,
+            InputAction::Down => self.down,
+            InputAction::Left => self.left,
+            InputAction::Right => self.right,
+            InputAction::Jump => self.jump,
+            InputAction::Attack => self.attack,
+            InputAction::Grab => self.grab,
+            InputAction::Tongue => self.tongue,
+        }
+    }
+
+    pub fn set(&mut self, action: InputAction, value: T) {
+        match action {
+            InputAction::Up => self.up = value,
+            InputAction::Down => self.down = value,
+            InputAction::Left => self.left = value,
+            InputAction::Right => self.right = value,
+            InputAction::Jump => self.jump = value,
+            InputAction::Attack => self.attack = value,
+            InputAction::Grab => self.grab = value,
+            InputAction::Tongue => self.tongue = value,
+        }
+    }
+
+    /// First of `actions` bound to `value`.
+    pub fn bound_action(&self, actions: &[InputAction], value: T) -> Option<InputAction> {
+        actions
+            .iter()
+            .copied()
+            .find(|&action| self.get(action) == value)
+    }
+
+    pub fn input(&self, pressed: impl Fn(T) -> bool) -> u16 {
+        InputAction::ALL
+            .iter()
+            .filter(|&&action| pressed(self.get(action)))
+            .fold(0, |input, action| input | action.input())
+    }
+}
+
+/// Keyboard keys and gamepad buttons driving one local player.
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+pub struct InputBindings {
+    pub keys: ActionBindings<KeyCode>,
+    pub buttons: ActionBindings<GamepadButtonType>,
+}
+
+impl InputBindings {
+    fn new(keys: ActionBindings<KeyCode>) -> Self {
+        Self {
+            keys,
+            buttons: ActionBindings {
+                up: GamepadButtonType::DPadUp,
+                down: GamepadButtonType::DPadDown,
+                left: GamepadButtonType::DPadLeft,
+                right: GamepadButtonType::DPadRight,
+                jump: GamepadButtonType::South,
+                attack: GamepadButtonType::West,
+                grab: GamepadButtonType::East,
+                tongue: GamepadButtonType::North,
+            },
+        }
+    }
+
+    pub fn path(local_player_index: usize) -> PathBuf {
+        Path::new(INPUT_BINDINGS_DIRECTORY).join(format!("player{}.ron", local_player_index))
+    }
+}
+
+/// Bindings as saved on disk, the actions added after the first release are missing from older files.
+#[derive(Deserialize)]
+struct SavedInputBindings {
+    keys: SavedActionBindings<KeyCode>,
+    buttons: SavedActionBindings<GamepadButtonType>,
+}
+
+#[derive(Deserialize)]
+#[serde(bound = "T: Deserialize<'de>")]
+struct SavedActionBindings<T> {
+    up: T,
+    down: T,
+    left: T,
+    right: T,
+    jump: T,
+    #[serde(default)]
+    attack: Option<T>,
+    #[serde(default)]
+    grab: Option<T>,
+    #[serde(default)]
+    tongue: Option<T>,
+}
+
+impl<T> SavedActionBindings<T> {
+    /// Fills the missing actions with the ones of `defaults`.
+    fn or(self, defaults: ActionBindings<T>) -> ActionBindings<T> {
+        ActionBindings {
+            up: self.up,
+            down: self.down,
+            left: self.left,
+            right: self.right,
+            jump: self.jump,
+            attack: self.attack.unwrap_or(defaults.attack),
+            grab: self.grab.unwrap_or(defaults.grab),
+            tongue: self.tongue.unwrap_or(defaults.tongue),
+        }
+    }
+}
+
+impl InputBindings {
+    /// Reads saved bindings, the actions missing from the file keep the ones of `defaults`.
+    fn from_ron(ron: &str, defaults: InputBindings) -> Result<Self, Box<dyn Error>> {
+        // saved files write the actions without `Some(..)`
+        let saved: SavedInputBindings = ron::Options::default()
+            .with_default_extension(ron::extensions::Extensions::IMPLICIT_SOME)
+            .from_str(ron)?;
+
+        Ok(Self {
+            keys: saved.keys.or(defaults.keys),
+            buttons: saved.buttons.or(defaults.buttons),
+        })
+    }
+}
+
+/// Input bindings of the local players, in the order of their player handles.
+#[derive(Deref, DerefMut, Resource)]
+pub struct InputBindingsRes(pub Vec<InputBindings>);
+
+impl Default for InputBindingsRes {
+    fn default() -> Self {
+        Self(vec![
+            InputBindings::new(ActionBindings {
+                up: KeyCode::Up,
+                down: KeyCode::Down,
+                left: KeyCode::Left,
+                right: KeyCode::Right,
+                jump: KeyCode::Space,
+                attack: KeyCode::RControl,
+                grab: KeyCode::RShift,
+                tongue: KeyCode::Return,
+            }),
+            InputBindings::new(ActionBindings {
+                up: KeyCode::W,
+                down: KeyCode::S,
+                left: KeyCode::A,
+                right: KeyCode::D,
+                jump: KeyCode::F,
+                attack: KeyCode::G,
+                grab: KeyCode::H,
+                tongue: KeyCode::R,
+            }),
+            InputBindings::new(ActionBindings {
+                up: KeyCode::Numpad8,
+                down: KeyCode::Numpad5,
+                left: KeyCode::Numpad4,
+                right: KeyCode::Numpad6,
+                jump: KeyCode::Numpad0,
+                attack: KeyCode::NumpadEnter,
+                grab: KeyCode::NumpadAdd,
+                tongue: KeyCode::NumpadSubtract,
+            }),
+        ])
+    }
+}
+
+impl InputBindingsRes {
+    /// Loads the saved bindings of every local player, players without a saved file keep the defaults.
+    pub fn load() -> Result<Self, Box<dyn Error>> {
+        let mut input_bindings = Self::default();
+
+        for (local_player_index, bindings) in input_bindings.iter_mut().enumerate() {
+            let path = InputBindings::path(local_player_index);
+            if path.exists() {
+                *bindings = InputBindings::from_ron(&fs::read_to_string(&path)?, *bindings)
+                    .map_err(|e| format!("{}: {}", path.display(), e))?;
+            }
+        }
+
+        for (local_player_index, bindings) in input_bindings.iter().enumerate() {
+            for (action_index, action) in InputAction::ALL.into_iter().enumerate() {
+                let previous_actions = &InputAction::ALL[..action_index];
+                let conflict = input_bindings
+                    .key_conflict(
+                        local_player_index,
+                        previous_actions,
+                        bindings.keys.get(action),
+                    )
+                    .or_else(|| {
+                        input_bindings.button_conflict(
+                            local_player_index,
+                            previous_actions,
+                            bindings.buttons.get(action),
+                        )
+                    });
+                if let Some(conflict) = conflict {
+                    return Err(conflict.into());
+                }
+            }
+        }
+
+        Ok(input_bindings)
+    }
+
+    pub fn save(&self, local_player_index: usize) -> Result<PathBuf, Box<dyn Error>> {
+        let path = InputBindings::path(local_player_index);
+        fs::create_dir_all(INPUT_BINDINGS_DIRECTORY)?;
+        fs::write(
+            &path,
+            ron::ser::to_string_pretty(&self[local_player_index], Default::default())?,
+        )?;
+
+        Ok(path)
+    }
+
+    /// Tells why `key` cannot be bound to a local player, keys are shared by every local player,
+    /// `own_actions` are the actions of this player to check.
+    pub fn key_conflict(
+        &self,
+        local_player_index: usize,
+        own_actions: &[InputAction],
+        key: KeyCode,
+    ) -> Option<String> {
+        self.iter()
+            .enumerate()
+            .find_map(|(other_index, bindings)| {
+                let actions = if other_index == local_player_index {
+                    own_actions
+                } else {
+                    &InputAction::ALL
+                };
+
+                bindings
+                    .keys
+                    .bound_action(actions, key)
+                    .map(|other_action| (other_index, other_action))
+            })
+            .map(|(other_index, other_action)| {
+                format!(
+                    "{:?} is already bound to {:?} of local player {}",
+                    key, other_action, other_index
+                )
+            })
+    }
+
+    /// Tells why `button` cannot be bound to a local player, every player has a gamepad of its own.
+    pub fn button_conflict(
+        &self,
+        local_player_index: usize,
+        own_actions: &[InputAction],
+        button: GamepadButtonType,
+    ) -> Option<String> {
+        self[local_player_index]
+            .buttons
+            .bound_action(own_actions, button)
+            .map(|other_action| {
+                format!(
+                    "{:?} is already bound to {:?} of local player {}",
+                    button, other_action, local_player_index
+                )
+            })
+    }
+}
+
+/// Rebinding in progress, actions are captured one after the other.
+pub struct Rebinding {
+    pub local_player_index: usize,
+    pub action_index: usize,
+    /// Bindings to restore when the rebinding is cancelled.
+    pub previous: InputBindings,
+    pub message: String,
+}
+
+#[derive(Default, Resource)]
+pub struct RebindingRes(pub Option<Rebinding>);
+
+#[derive(Component)]
+pub struct RebindingText;
+
+/// Gamepads driving the local players, in the order of their player handles, filled as gamepads connect.
+#[derive(Default, Deref, DerefMut, Resource)]
+pub struct GamepadAssignmentsRes(pub Vec<Option<Gamepad>>);
+
+impl GamepadAssignmentsRes {
+    pub fn assign(&mut self, gamepad: Gamepad) -> usize {
+        if let Some(index) = self.iter().position(|assigned| *assigned == Some(gamepad)) {
+            return index;
+        }
+
+        match self.iter().position(Option::is_none) {
+            Some(index) => {
+                self[index] = Some(gamepad);
+                index
+            }
+            None => {
+                self.push(Some(gamepad));
+                self.len() - 1
+            }
+        }
+    }
+
+    pub fn unassign(&mut self, gamepad: Gamepad) -> Option<usize> {
+        let index = self
+            .iter()
+            .position(|assigned| *assigned == Some(gamepad))?;
+        self[index] = None;
+
+        Some(index)
+    }
+}
+
+/// Inputs of every player for the frame being simulated, gameplay systems read their inputs from here
+/// so the rollback schedule can be driven by GGRS as well as by a replay.
+#[derive(Default, Deref, DerefMut, Resource)]
+pub struct FrameInputsRes(pub Vec<(BoxInput, InputStatus)>);
+
+/// Deterministic input source replacing the keyboard when running headless.
+#[allow(dead_code)]
+#[derive(Resource)]
+pub enum ScriptedInputRes {
+    Seeded {
+        rng: Box<StdRng>,
+    },
+    Script {
+        frames: Vec<Vec<BoxInput>>,
+        cursors: Vec<usize>,
+    },
+}
+
+#[allow(dead_code)]
+impl ScriptedInputRes {
+    pub fn from_seed(seed: u64) -> Self {
+        Self::Seeded {
+            rng: Box::new(StdRng::seed_from_u64(seed)),
+        }
+    }
+
+    /// Loads an input script, each line holds a number of frames followed by the buttons held by each player:
+    ///
+    /// ```text
+    /// # frames  player 0    player 1
+    /// 60        right       left
+    /// 20        right+jump  -
+    /// ```
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut frames = Vec::new();
+
+        for (line_index, line) in fs::read_to_string(path)?.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let mut tokens = line.split_whitespace();
+            let count: usize = tokens
+                .next()
+                .unwrap()
+                .parse()
+                .map_err(|e| format!("{}:{}: {}", path.display(), line_index + 1, e))?;
+            let inputs = tokens
+                .map(|token| parse_input_buttons(token).map(BoxInput::from_buttons))
+                .collect::<Result<Vec<BoxInput>, String>>()
+                .map_err(|e| format!("{}:{}: {}", path.display(), line_index + 1, e))?;
+
+            frames.extend(std::iter::repeat_n(inputs, count));
+        }
+
+        Ok(Self::Script {
+            frames,
+            cursors: Vec::new(),
+        })
+    }
+
+    pub fn next_input(&mut self, handle: PlayerHandle) -> BoxInput {
+        match self {
+            Self::Seeded { rng } => {
+                let mut input = BoxInput::from_buttons(rng.gen::<u16>() & INPUT_ALL);
+                // exercise the analog range as well
+                input.axis_x = (input.axis_x as i16 * rng.gen_range(1..=127) / 127) as i8;
+                input.axis_y = (input.axis_y as i16 * rng.gen_range(1..=127) / 127) as i8;
+                input
+            }
+            Self::Script { frames, cursors } => {
+                if cursors.len() <= handle {
+                    cursors.resize(handle + 1, 0);
+                }
+                let frame = cursors[handle];
+                cursors[handle] += 1;
+
+                frames
+                    .get(frame)
+                    .and_then(|inputs| inputs.get(handle))
+                    .copied()
+                    .unwrap_or_else(BoxInput::zeroed)
+            }
+        }
+    }
+}
+
+#[allow(dead_code)]
+fn parse_input_buttons(token: &str) -> Result<u16, String> {
+    if token == "-" {
+        return Ok(0);
+    }
+
+    token.split('+').try_fold(0, |input, button| match button {
+        "up" => Ok(input | INPUT_UP),
+        "down" => Ok(input | INPUT_DOWN),
+        "left" => Ok(input | INPUT_LEFT),
+        "right" => Ok(input | INPUT_RIGHT),
+        "jump" => Ok(input | INPUT_JUMP),
+        "attack" => Ok(input | INPUT_ATTACK),
+        "grab" => Ok(input | INPUT_GRAB),
+        "tongue" => Ok(input | INPUT_TONGUE),
+        _ => Err(format!("unknown button {:?}", button)),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn saved_bindings_without_the_new_actions_keep_the_defaults() {
+        let defaults = InputBindingsRes::default()[1];
+        let ron = "(
+            keys: (up: Up, down: Down, left: Left, right: Right, jump: Space),
+            buttons: (up: DPadUp, down: DPadDown, left: DPadLeft, right: DPadRight, jump: South),
+        )";
+
+        let bindings = InputBindings::from_ron(ron, defaults).unwrap();
+
+        assert_eq!(bindings.keys.up, KeyCode::Up);
+        assert_eq!(bindings.keys.attack, defaults.keys.attack);
+        assert_eq!(bindings.keys.grab, defaults.keys.grab);
+        assert_eq!(bindings.keys.tongue, defaults.keys.tongue);
+        assert_eq!(bindings.buttons, defaults.buttons);
+    }
+
+    #[test]
+    fn saved_bindings_round_trip() {
+        let mut bindings = InputBindingsRes::default()[0];
+        bindings.keys.attack = KeyCode::X;
+        let ron = ron::ser::to_string_pretty(&bindings, Default::default()).unwrap();
+
+        let loaded = InputBindings::from_ron(&ron, InputBindingsRes::default()[0]).unwrap();
+
+        assert_eq!(loaded.keys, bindings.keys);
+        assert_eq!(loaded.buttons, bindings.buttons);
+    }
+
+    #[test]
+    fn from_buttons_deflects_the_axes_of_the_pressed_directions() {
+        let input = BoxInput::from_buttons(INPUT_LEFT | INPUT_UP | INPUT_JUMP);
+        assert_eq!(input.inp, INPUT_LEFT | INPUT_UP | INPUT_JUMP);
+        assert_eq!(input.axis_x, -INPUT_AXIS_MAX);
+        assert_eq!(input.axis_y, INPUT_AXIS_MAX);
+
+        // opposite directions cancel out
+        let input = BoxInput::from_buttons(INPUT_LEFT | INPUT_RIGHT | INPUT_DOWN);
+        assert_eq!(input.axis_x, 0);
+        assert_eq!(input.axis_y, -INPUT_AXIS_MAX);
+    }
+
+    #[test]
+    fn quantize_axis_clamps_and_rounds() {
+        assert_eq!(BoxInput::quantize_axis(0.0), 0);
+        assert_eq!(BoxInput::quantize_axis(0.5), 64);
+        assert_eq!(BoxInput::quantize_axis(-0.5), -64);
+        assert_eq!(BoxInput::quantize_axis(1.0), INPUT_AXIS_MAX);
+        assert_eq!(BoxInput::quantize_axis(-3.0), -INPUT_AXIS_MAX);
+        assert_eq!(BoxInput::quantize_axis(3.0), INPUT_AXIS_MAX);
+    }
+
+    #[test]
+    fn merge_keeps_the_buttons_of_both_and_the_most_deflected_axes() {
+        let keyboard = BoxInput::from_buttons(INPUT_RIGHT | INPUT_JUMP);
+        let gamepad = BoxInput {
+            inp: INPUT_ATTACK,
+            axis_x: -40,
+            axis_y: -90,
+        };
+
+        let merged = keyboard.merge(gamepad);
+
+        assert_eq!(merged.inp, INPUT_RIGHT | INPUT_JUMP | INPUT_ATTACK);
+        assert_eq!(merged.axis_x, INPUT_AXIS_MAX);
+        assert_eq!(merged.axis_y, -90);
+    }
+
+    #[test]
+    fn gamepads_fill_the_free_slots_first() {
+        let mut assignments = GamepadAssignmentsRes::default();
+
+        assert_eq!(assignments.assign(Gamepad::new(3)), 0);
+        assert_eq!(assignments.assign(Gamepad::new(5)), 1);
+        assert_eq!(assignments.assign(Gamepad::new(3)), 0);
+
+        assert_eq!(assignments.unassign(Gamepad::new(3)), Some(0));
+        assert_eq!(assignments.unassign(Gamepad::new(3)), None);
+
+        assert_eq!(assignments.assign(Gamepad::new(7)), 0);
+        assert_eq!(assignments.assign(Gamepad::new(3)), 2);
+        assert_eq!(
+            assignments.0,
+            vec![
+                Some(Gamepad::new(7)),
+                Some(Gamepad::new(5)),
+                Some(Gamepad::new(3))
+            ]
+        );
+    }
 }
@@ -1,28 +1,239 @@
 use bevy::prelude::*;
+use bevy_ggrs::Session;
+use bytemuck::Zeroable;
 use ggrs::PlayerHandle;
+use log::{error, info};
 
 use crate::game::core::input::structs::{
-    BoxInput, INPUT_DOWN, INPUT_JUMP, INPUT_LEFT, INPUT_RIGHT, INPUT_UP,
+    BoxInput, FrameInputsRes, GamepadAssignmentsRes, InputAction, InputBindingsRes,
+    PreviousInput2D, Rebinding, RebindingRes, RebindingText, ScriptedInputRes,
 };
+use crate::game::core::input::utilities::{gamepad_input, local_player_index};
+use crate::game::core::settings::structs::GameSettings;
+use crate::game::player::structs::Player2D;
+use crate::game::GameConfig;
 
-pub fn input_system(_handle: In<PlayerHandle>, keyboard_input: Res<Input<KeyCode>>) -> BoxInput {
-    let mut input: u8 = 0;
-
-    if keyboard_input.pressed(KeyCode::Up) {
-        input |= INPUT_UP;
+#[allow(clippy::too_many_arguments)]
+pub fn input_system(
+    handle: In<PlayerHandle>,
+    session: Res<Session<GameConfig>>,
+    settings: Res<GameSettings>,
+    rebinding: Res<RebindingRes>,
+    keyboard_input: Res<Input<KeyCode>>,
+    input_bindings: Res<InputBindingsRes>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_axes: Res<Axis<GamepadAxis>>,
+    gamepad_assignments: Res<GamepadAssignmentsRes>,
+) -> BoxInput {
+    // keys pressed on the rebinding screen must not move the frogs
+    if rebinding.0.is_some() {
+        return BoxInput::zeroed();
     }
-    if keyboard_input.pressed(KeyCode::Down) {
-        input |= INPUT_DOWN;
+
+    let local_player_index = match local_player_index(&session, handle.0) {
+        Some(local_player_index) => local_player_index,
+        None => return BoxInput::zeroed(),
+    };
+    let bindings = match input_bindings.get(local_player_index) {
+        Some(bindings) => bindings,
+        None => return BoxInput::zeroed(),
+    };
+
+    let mut input = BoxInput::from_buttons(bindings.keys.input(|key| keyboard_input.pressed(key)));
+
+    if let Some(Some(gamepad)) = gamepad_assignments.get(local_player_index) {
+        input = input.merge(gamepad_input(
+            *gamepad,
+            &bindings.buttons,
+            &gamepad_buttons,
+            &gamepad_axes,
+            settings.gamepad_deadzone,
+        ));
     }
-    if keyboard_input.pressed(KeyCode::Left) {
-        input |= INPUT_LEFT;
+
+    input
+}
+
+pub fn gamepad_assignment_system(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut gamepad_assignments: ResMut<GamepadAssignmentsRes>,
+) {
+    for gamepad_event in gamepad_events.iter() {
+        match gamepad_event.event_type {
+            GamepadEventType::Connected(_) => {
+                let index = gamepad_assignments.assign(gamepad_event.gamepad);
+                info!(
+                    "gamepad {:?} connected, assigned to local player {}",
+                    gamepad_event.gamepad, index
+                );
+            }
+            GamepadEventType::Disconnected => {
+                if let Some(index) = gamepad_assignments.unassign(gamepad_event.gamepad) {
+                    info!(
+                        "gamepad {:?} disconnected, local player {} is keyboard only",
+                        gamepad_event.gamepad, index
+                    );
+                }
+            }
+            _ => (),
+        }
     }
-    if keyboard_input.pressed(KeyCode::Right) {
-        input |= INPUT_RIGHT;
+}
+
+#[allow(dead_code)]
+pub fn scripted_input_system(
+    handle: In<PlayerHandle>,
+    mut scripted_input: ResMut<ScriptedInputRes>,
+) -> BoxInput {
+    scripted_input.next_input(handle.0)
+}
+
+/// Runs after the gameplay systems so they compare the inputs of this frame with the ones of the previous frame.
+pub fn previous_input_system(
+    inputs: Res<FrameInputsRes>,
+    mut query: Query<(&Player2D, &mut PreviousInput2D)>,
+) {
+    for (player, mut previous_input) in query.iter_mut() {
+        let (input, _) = inputs[player.handle];
+        previous_input.inp = input.inp;
     }
-    if keyboard_input.pressed(KeyCode::Space) {
-        input |= INPUT_JUMP;
+}
+
+pub fn startup_rebinding_system(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let font_handle: Handle<Font> = asset_server.load("fonts/Pixellari.ttf");
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_handle,
+                color: Color::WHITE,
+                font_size: 20.0,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                bottom: Val::Px(80.0),
+                left: Val::Percent(25.0),
+                ..default()
+            },
+            ..default()
+        }),
+        RebindingText,
+    ));
+}
+
+/// F3 opens the rebinding screen, every action of a local player is bound to the next key or gamepad button
+/// pressed, Tab switches to the next local player and Escape cancels.
+pub fn rebinding_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    gamepad_buttons: Res<Input<GamepadButton>>,
+    gamepad_assignments: Res<GamepadAssignmentsRes>,
+    //
+    mut input_bindings: ResMut<InputBindingsRes>,
+    mut rebinding: ResMut<RebindingRes>,
+    mut query_text: Query<&mut Text, With<RebindingText>>,
+) {
+    const RESERVED_KEYS: [KeyCode; 3] = [KeyCode::F3, KeyCode::Tab, KeyCode::Escape];
+
+    if rebinding.0.is_none() && keyboard_input.just_pressed(KeyCode::F3) {
+        rebinding.0 = Some(Rebinding {
+            local_player_index: 0,
+            action_index: 0,
+            previous: input_bindings[0],
+            message: String::new(),
+        });
+    } else if let Some(current) = &mut rebinding.0 {
+        let local_player_index = current.local_player_index;
+
+        if keyboard_input.just_pressed(KeyCode::Escape) {
+            input_bindings[local_player_index] = current.previous;
+            rebinding.0 = None;
+        } else if keyboard_input.just_pressed(KeyCode::Tab) {
+            input_bindings[local_player_index] = current.previous;
+
+            let local_player_index = (local_player_index + 1) % input_bindings.len();
+            *current = Rebinding {
+                local_player_index,
+                action_index: 0,
+                previous: input_bindings[local_player_index],
+                message: String::new(),
+            };
+        } else {
+            let action = InputAction::ALL[current.action_index];
+            let previous_actions = &InputAction::ALL[..current.action_index];
+
+            let pressed_key = keyboard_input
+                .get_just_pressed()
+                .find(|&&key| !RESERVED_KEYS.contains(&key))
+                .copied();
+            // only the gamepad of the player can rebind its buttons, any gamepad if it has none
+            let assigned_gamepad = gamepad_assignments
+                .get(local_player_index)
+                .copied()
+                .flatten();
+            let pressed_button = gamepad_buttons
+                .get_just_pressed()
+                .find(|button| assigned_gamepad.is_none_or(|gamepad| button.gamepad == gamepad))
+                .map(|button| button.button_type);
+
+            let conflict = if let Some(key) = pressed_key {
+                let conflict =
+                    input_bindings.key_conflict(local_player_index, previous_actions, key);
+                if conflict.is_none() {
+                    input_bindings[local_player_index].keys.set(action, key);
+                }
+                Some(conflict)
+            } else if let Some(button) = pressed_button {
+                let conflict =
+                    input_bindings.button_conflict(local_player_index, previous_actions, button);
+                if conflict.is_none() {
+                    input_bindings[local_player_index]
+                        .buttons
+                        .set(action, button);
+                }
+                Some(conflict)
+            } else {
+                None
+            };
+
+            match conflict {
+                Some(Some(conflict)) => current.message = conflict,
+                Some(None) => {
+                    current.message.clear();
+                    current.action_index += 1;
+                }
+                None => (),
+            }
+
+            if current.action_index == InputAction::ALL.len() {
+                match input_bindings.save(local_player_index) {
+                    Ok(path) => info!(
+                        "input bindings of local player {} saved to {}",
+                        local_player_index,
+                        path.display()
+                    ),
+                    Err(e) => error!(
+                        "failed to save the input bindings of local player {}: {}",
+                        local_player_index, e
+                    ),
+                }
+                rebinding.0 = None;
+            }
+        }
     }
 
-    BoxInput { inp: input }
+    let text = match &rebinding.0 {
+        Some(current) => format!(
+            "local player {}: press a key or a button for {:?}\n{}\n(tab: next player, escape: cancel)",
+            current.local_player_index,
+            InputAction::ALL[current.action_index],
+            current.message
+        ),
+        None => String::new(),
+    };
+    for mut rebinding_text in query_text.iter_mut() {
+        rebinding_text.sections[0].value = text.clone();
+    }
 }
@@ -0,0 +1,73 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use ggrs::PlayerHandle;
+
+use crate::game::core::input::structs::{
+    ActionBindings, BoxInput, INPUT_DOWN, INPUT_LEFT, INPUT_RIGHT, INPUT_UP,
+};
+use crate::game::GameConfig;
+
+/// Position of a player among the players of this machine, it picks the input bindings of the player.
+pub fn local_player_index(session: &Session<GameConfig>, handle: PlayerHandle) -> Option<usize> {
+    match session {
+        Session::P2PSession(s) => {
+            let mut local_handles = s.local_player_handles();
+            local_handles.sort_unstable();
+            local_handles
+                .iter()
+                .position(|&local_handle| local_handle == handle)
+        }
+        // every player of a synctest is local
+        Session::SyncTestSession(_) => Some(handle),
+        Session::SpectatorSession(_) => None,
+    }
+}
+
+/// Reads the buttons bound to the actions and the left stick of a gamepad.
+pub fn gamepad_input(
+    gamepad: Gamepad,
+    bindings: &ActionBindings<GamepadButtonType>,
+    gamepad_buttons: &Input<GamepadButton>,
+    gamepad_axes: &Axis<GamepadAxis>,
+    deadzone: f32,
+) -> BoxInput {
+    let buttons =
+        BoxInput::from_buttons(bindings.input(|button_type| {
+            gamepad_buttons.pressed(GamepadButton::new(gamepad, button_type))
+        }));
+
+    // rescale the stick so it starts from zero at the edge of the deadzone
+    let axis = |axis_type| {
+        let value = gamepad_axes
+            .get(GamepadAxis::new(gamepad, axis_type))
+            .unwrap_or_default();
+        if value.abs() <= deadzone {
+            0.0
+        } else {
+            value.signum() * (value.abs() - deadzone) / (1.0 - deadzone)
+        }
+    };
+    let stick_x = axis(GamepadAxisType::LeftStickX);
+    let stick_y = axis(GamepadAxisType::LeftStickY);
+
+    let mut inp = 0;
+    if stick_y > 0.0 {
+        inp |= INPUT_UP;
+    }
+    if stick_y < 0.0 {
+        inp |= INPUT_DOWN;
+    }
+    if stick_x < 0.0 {
+        inp |= INPUT_LEFT;
+    }
+    if stick_x > 0.0 {
+        inp |= INPUT_RIGHT;
+    }
+    let stick = BoxInput {
+        inp,
+        axis_x: BoxInput::quantize_axis(stick_x),
+        axis_y: BoxInput::quantize_axis(stick_y),
+    };
+
+    buttons.merge(stick)
+}
@@ -0,0 +1,258 @@
+use bevy::prelude::*;
+use derive_more::{Deref, DerefMut};
+use ggrs::PlayerHandle;
+use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs;
+use std::path::Path;
+
+use crate::game::core::maths::structs::{Fixed, Transform2D, Vector2D};
+use crate::game::core::physics::hooks::PhysicsSurface2D;
+use crate::game::core::physics::structs::ColliderShape2D;
+
+/// Static collider of a level, positions and sizes in meters.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelCollider {
+    pub position: (f32, f32),
+    #[serde(default)]
+    pub rotation: f32,
+    pub shape: ColliderShape2D,
+    /// Only blocks from above, the frogs jump up through it.
+    #[serde(default)]
+    pub one_way: bool,
+    #[serde(default)]
+    pub restitution: f32,
+    /// Friction of every contact with the collider, whatever the friction of the other collider.
+    #[serde(default)]
+    pub friction: Option<f32>,
+    /// Speed the collider carries the bodies at along its top, in meters per second.
+    #[serde(default)]
+    pub conveyor_speed: Option<f32>,
+}
+
+impl LevelCollider {
+    pub fn transform(&self) -> Transform2D {
+        Transform2D {
+            position: Vector2D::from_f32(self.position.0, self.position.1),
+            rotation: Fixed::from_f32(self.rotation),
+            ..default()
+        }
+    }
+
+    pub fn surface(&self) -> PhysicsSurface2D {
+        PhysicsSurface2D {
+            one_way: self.one_way,
+            conveyor_speed: self.conveyor_speed,
+            friction: self.friction,
+        }
+    }
+}
+
+/// Decoration drawn behind the game, it takes no part in the simulation.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelSprite {
+    /// Path in the assets directory.
+    pub texture: String,
+    pub position: (f32, f32),
+    /// Size in meters, the size of the texture when missing.
+    #[serde(default)]
+    pub size: Option<(f32, f32)>,
+    #[serde(default)]
+    pub z: f32,
+}
+
+/// Everything placed in the world at the start of a match, every peer of a session must load the same level.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Level {
+    pub name: String,
+    pub colliders: Vec<LevelCollider>,
+    /// Spawn point of every player handle, in meters.
+    pub player_spawns: Vec<(f32, f32)>,
+    #[serde(default)]
+    pub ball_spawns: Vec<(f32, f32)>,
+    #[serde(default)]
+    pub sprites: Vec<LevelSprite>,
+    /// Area the cameras stay in as `(min, max)` in meters, the box around the colliders and spawns when missing.
+    #[serde(default)]
+    pub camera_bounds: Option<((f32, f32), (f32, f32))>,
+}
+
+impl Default for Level {
+    fn default() -> Self {
+        Self {
+            name: "default".to_string(),
+            colliders: vec![
+                LevelCollider {
+                    position: (0.0, -10.0),
+                    rotation: 0.0,
+                    shape: ColliderShape2D::Cuboid {
+                        half_width: 20.0,
+                        half_height: 1.0,
+                    },
+                    one_way: false,
+                    restitution: 0.0,
+                    friction: None,
+                    conveyor_speed: None,
+                },
+                LevelCollider {
+                    position: (0.0, -6.0),
+                    rotation: 0.0,
+                    shape: ColliderShape2D::Cuboid {
+                        half_width: 3.0,
+                        half_height: 0.1,
+                    },
+                    one_way: true,
+                    restitution: 0.0,
+                    friction: None,
+                    conveyor_speed: None,
+                },
+            ],
+            player_spawns: vec![(0.0, 10.0), (5.0, 10.0), (10.0, 10.0), (15.0, 10.0)],
+            ball_spawns: vec![(5.0, 3.0)],
+            sprites: Vec::new(),
+            camera_bounds: Some(((-20.0, -11.0), (20.0, 20.0))),
+        }
+    }
+}
+
+impl Level {
+    /// Reads a `.ron` level, the default level when no path is given.
+    pub fn load(path: Option<&Path>) -> Result<Self, Box<dyn Error>> {
+        let level: Level = match path {
+            Some(path) => {
+                let contents = fs::read_to_string(path)
+                    .map_err(|err| format!("cannot read level {}: {}", path.display(), err))?;
+                ron::from_str(&contents)
+                    .map_err(|err| format!("invalid level {}: {}", path.display(), err))?
+            }
+            None => Level::default(),
+        };
+
+        level.validate()?;
+
+        Ok(level)
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        if self.player_spawns.is_empty() {
+            return Err(format!("level {} has no player spawn", self.name).into());
+        }
+        for (i, collider) in self.colliders.iter().enumerate() {
+            collider
+                .shape
+                .validate()
+                .map_err(|err| format!("level {}, collider {}: {}", self.name, i, err))?;
+        }
+
+        Ok(())
+    }
+
+    /// Hash of the parsed level, equal on every machine for the same level whatever the formatting of its file.
+    pub fn checksum(&self) -> u64 {
+        let bytes = bincode::serialize(self).expect("failed to serialize level");
+
+        // FNV-1a, unlike the std hashers it is guaranteed to stay the same across Rust versions
+        bytes.iter().fold(0xcbf29ce484222325, |hash, &byte| {
+            (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    /// Camera bounds in meters.
+    pub fn bounds(&self) -> ((f32, f32), (f32, f32)) {
+        if let Some(camera_bounds) = self.camera_bounds {
+            return camera_bounds;
+        }
+
+        let mut points = Vec::new();
+        for collider in self.colliders.iter() {
+            let position = Isometry::new(
+                vector![collider.position.0, collider.position.1],
+                collider.rotation,
+            );
+            let aabb = collider.shape.shape().compute_aabb(&position);
+            points.push((aabb.mins.x, aabb.mins.y));
+            points.push((aabb.maxs.x, aabb.maxs.y));
+        }
+        points.extend(self.player_spawns.iter().chain(self.ball_spawns.iter()));
+
+        points.iter().fold(
+            ((f32::MAX, f32::MAX), (f32::MIN, f32::MIN)),
+            |((min_x, min_y), (max_x, max_y)), &(x, y)| {
+                ((min_x.min(x), min_y.min(y)), (max_x.max(x), max_y.max(y)))
+            },
+        )
+    }
+
+    /// Players beyond the number of spawn points share them, in order.
+    pub fn player_spawn(&self, handle: PlayerHandle) -> Transform2D {
+        let (x, y) = self.player_spawns[handle % self.player_spawns.len()];
+        Transform2D::from_position(Vector2D::from_f32(x, y))
+    }
+}
+
+/// Level of the running match, never modified once the match started so it is not rolled back.
+#[derive(Deref, DerefMut, Resource)]
+pub struct LevelRes(pub Level);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn level_with_polygon(points: Vec<(f32, f32)>) -> Level {
+        let mut level = Level::default();
+        level.colliders[0].shape = ColliderShape2D::ConvexPolygon { points };
+        level
+    }
+
+    #[test]
+    fn validate_accepts_convex_polygons() {
+        let level = level_with_polygon(vec![(0.0, 0.0), (2.0, 0.0), (1.0, 1.0)]);
+
+        assert!(level.validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_degenerated_polygons() {
+        for points in [
+            vec![],
+            vec![(0.0, 0.0), (1.0, 0.0)],
+            vec![(0.0, 0.0), (1.0, 1.0), (2.0, 2.0)],
+        ] {
+            assert!(level_with_polygon(points).validate().is_err());
+        }
+    }
+
+    #[test]
+    fn checksum_ignores_the_formatting_of_the_file() {
+        let level = Level::default();
+        let pretty = ron::ser::to_string_pretty(&level, Default::default()).unwrap();
+        let compact = ron::to_string(&level).unwrap();
+
+        assert_eq!(
+            ron::from_str::<Level>(&pretty).unwrap().checksum(),
+            level.checksum()
+        );
+        assert_eq!(
+            ron::from_str::<Level>(&compact).unwrap().checksum(),
+            level.checksum()
+        );
+    }
+
+    #[test]
+    fn checksum_changes_with_the_level() {
+        let mut level = Level::default();
+        level.colliders[1].one_way = false;
+
+        assert_ne!(level.checksum(), Level::default().checksum());
+    }
+
+    #[test]
+    fn checksum_of_the_default_level_is_stable() {
+        // peers built by other compilers must agree, update it only along with the default level
+        assert_eq!(Level::default().checksum(), 0x52d6ded333f94029);
+    }
+}
@@ -0,0 +1,71 @@
+use bevy::prelude::*;
+use bevy_ggrs::RollbackIdProvider;
+use rapier2d::prelude::*;
+
+use crate::game::core::level::structs::LevelRes;
+use crate::game::core::physics::range::scale_physics;
+use crate::game::core::physics::structs::{ColliderDesc2D, RigidBodyDesc2D};
+use crate::game::core::physics::systems::{
+    ONE_WAY_PHYSICS_GROUP, PLAYER_PHYSICS_GROUP, SOLID_PHYSICS_GROUP,
+};
+
+pub fn startup_level_system(
+    level: Res<LevelRes>,
+    //
+    mut commands: Commands,
+    mut rollback_id_provider: ResMut<RollbackIdProvider>,
+) {
+    for collider in level.colliders.iter() {
+        // the character controller handles the frogs on one-way platforms, the physics hooks the dynamic bodies
+        let (memberships, filter) = if collider.one_way {
+            (ONE_WAY_PHYSICS_GROUP, PLAYER_PHYSICS_GROUP)
+        } else {
+            (
+                SOLID_PHYSICS_GROUP,
+                SOLID_PHYSICS_GROUP | PLAYER_PHYSICS_GROUP,
+            )
+        };
+
+        commands.spawn((
+            Transform::default(),
+            GlobalTransform::default(),
+            collider.transform(),
+            RigidBodyDesc2D::new(RigidBodyType::Fixed),
+            ColliderDesc2D::new(collider.shape.clone())
+                .restitution(collider.restitution)
+                .collision_groups(memberships, filter)
+                .surface(collider.surface()),
+            rollback_id_provider.next(),
+        ));
+    }
+}
+
+/// Spawns the decorations of the level, only when the game has a window.
+pub fn startup_level_sprites_system(
+    level: Res<LevelRes>,
+    asset_server: Res<AssetServer>,
+    //
+    mut commands: Commands,
+) {
+    for sprite in level.sprites.iter() {
+        commands.spawn(SpriteBundle {
+            texture: asset_server.load(sprite.texture.as_str()),
+            sprite: Sprite {
+                custom_size: sprite.size.map(|(width, height)| {
+                    Vec2::new(
+                        scale_physics(width).to_f32(),
+                        scale_physics(height).to_f32(),
+                    )
+                }),
+                ..default()
+            },
+            transform: Transform::from_xyz(
+                scale_physics(sprite.position.0).to_f32(),
+                scale_physics(sprite.position.1).to_f32(),
+                sprite.z,
+            ),
+            //
+            ..default()
+        });
+    }
+}
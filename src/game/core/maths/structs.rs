@@ -1,68 +1,243 @@
 use bevy::prelude::*;
+use bevy::reflect::impl_reflect_value;
+use fixed::types::I32F32;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::ops;
 
-#[derive(Default, Reflect)]
+/// Fixed-point number used by the gameplay state, so the simulation does not depend on float determinism.
+/// Floats only come in from rapier and go out to the renderer, through `from_f32` and `to_f32`.
+#[derive(
+    Clone, Copy, Debug, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+pub struct Fixed(pub I32F32);
+
+#[allow(dead_code)]
+impl Fixed {
+    pub const ZERO: Fixed = Fixed::from_int(0);
+    pub const ONE: Fixed = Fixed::from_int(1);
+
+    #[inline]
+    pub const fn from_int(value: i32) -> Self {
+        Self(I32F32::from_bits((value as i64) << 32))
+    }
+    /// Exact enough for the tuning constants, `from_ratio(3, 50)` is 0.06.
+    #[inline]
+    pub const fn from_ratio(numerator: i32, denominator: i32) -> Self {
+        Self(I32F32::from_bits(
+            ((numerator as i64) << 32) / denominator as i64,
+        ))
+    }
+    /// Out of range values saturate, NaN from a degenerated rapier computation becomes zero instead of panicking.
+    #[inline]
+    pub fn from_f32(value: f32) -> Self {
+        if value.is_nan() {
+            return Self::ZERO;
+        }
+        Self(I32F32::saturating_from_num(value))
+    }
+
+    #[inline]
+    pub fn to_f32(self) -> f32 {
+        self.0.to_num()
+    }
+    /// Rounds towards zero.
+    #[inline]
+    pub fn to_i32(self) -> i32 {
+        self.0.round_to_zero().to_num()
+    }
+
+    /// Rounds half away from zero.
+    #[inline]
+    pub fn round(self) -> Self {
+        Self(self.0.round())
+    }
+
+    #[inline]
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+    #[inline]
+    pub fn signum(self) -> Self {
+        Self(self.0.signum())
+    }
+    #[inline]
+    pub fn is_zero(self) -> bool {
+        self.0 == I32F32::ZERO
+    }
+}
+
+impl fmt::Display for Fixed {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl From<i32> for Fixed {
+    fn from(value: i32) -> Self {
+        Self::from_int(value)
+    }
+}
+
+macro_rules! impl_fixed_op {
+    ($op:ident, $fn:ident, $op_assign:ident, $fn_assign:ident) => {
+        impl ops::$op for Fixed {
+            type Output = Fixed;
+
+            #[inline]
+            fn $fn(self, other: Fixed) -> Fixed {
+                Fixed(ops::$op::$fn(self.0, other.0))
+            }
+        }
+
+        impl ops::$op_assign for Fixed {
+            #[inline]
+            fn $fn_assign(&mut self, other: Fixed) {
+                ops::$op_assign::$fn_assign(&mut self.0, other.0);
+            }
+        }
+    };
+}
+
+impl_fixed_op!(Add, add, AddAssign, add_assign);
+impl_fixed_op!(Sub, sub, SubAssign, sub_assign);
+impl_fixed_op!(Mul, mul, MulAssign, mul_assign);
+impl_fixed_op!(Div, div, DivAssign, div_assign);
+impl_fixed_op!(Rem, rem, RemAssign, rem_assign);
+
+impl ops::Neg for Fixed {
+    type Output = Fixed;
+
+    #[inline]
+    fn neg(self) -> Fixed {
+        Fixed(-self.0)
+    }
+}
+
+impl_reflect_value!(Fixed(Debug, Hash, PartialEq, Serialize, Deserialize));
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, Reflect, Serialize, Deserialize)]
+#[reflect(Hash)]
 pub struct Vector2D {
-    pub x: f32,
-    pub y: f32,
+    pub x: Fixed,
+    pub y: Fixed,
 }
 
 #[allow(dead_code)]
 impl Vector2D {
+    pub const ZERO: Vector2D = Vector2D {
+        x: Fixed::ZERO,
+        y: Fixed::ZERO,
+    };
+
     #[inline]
-    pub fn new(x: f32, y: f32) -> Self {
+    pub fn new(x: Fixed, y: Fixed) -> Self {
         Self { x, y }
     }
+    #[inline]
+    pub fn from_f32(x: f32, y: f32) -> Self {
+        Self::new(Fixed::from_f32(x), Fixed::from_f32(y))
+    }
+    #[inline]
+    pub fn to_vec2(self) -> Vec2 {
+        Vec2::new(self.x.to_f32(), self.y.to_f32())
+    }
 
     #[inline]
-    pub fn set(&mut self, x: f32, y: f32) -> &mut Self {
+    pub fn set(&mut self, x: Fixed, y: Fixed) -> &mut Self {
         self.x = x;
         self.y = y;
         self
     }
     #[inline]
-    pub fn set_x(&mut self, value: f32) -> &mut Self {
+    pub fn set_x(&mut self, value: Fixed) -> &mut Self {
         self.x = value;
         self
     }
     #[inline]
-    pub fn set_y(&mut self, value: f32) -> &mut Self {
+    pub fn set_y(&mut self, value: Fixed) -> &mut Self {
         self.y = value;
         self
     }
-    pub fn set_all(&mut self, value: f32) -> &mut Self {
+    pub fn set_all(&mut self, value: Fixed) -> &mut Self {
         self.x = value;
         self.y = value;
         self
     }
 
     #[inline]
-    pub fn add(&mut self, x: f32, y: f32) -> &mut Self {
+    pub fn add(&mut self, x: Fixed, y: Fixed) -> &mut Self {
         self.x += x;
         self.y += y;
         self
     }
     #[inline]
-    pub fn add_x(&mut self, value: f32) -> &mut Self {
+    pub fn add_x(&mut self, value: Fixed) -> &mut Self {
         self.x += value;
         self
     }
     #[inline]
-    pub fn add_y(&mut self, value: f32) -> &mut Self {
+    pub fn add_y(&mut self, value: Fixed) -> &mut Self {
         self.y += value;
         self
     }
-    pub fn add_all(&mut self, value: f32) -> &mut Self {
+    pub fn add_all(&mut self, value: Fixed) -> &mut Self {
         self.x += value;
         self.y += value;
         self
     }
 }
 
-#[derive(Default, Reflect, Component)]
+impl ops::Add for Vector2D {
+    type Output = Vector2D;
+
+    fn add(self, other: Vector2D) -> Vector2D {
+        Vector2D::new(self.x + other.x, self.y + other.y)
+    }
+}
+
+impl ops::Sub for Vector2D {
+    type Output = Vector2D;
+
+    fn sub(self, other: Vector2D) -> Vector2D {
+        Vector2D::new(self.x - other.x, self.y - other.y)
+    }
+}
+
+impl ops::Mul<Fixed> for Vector2D {
+    type Output = Vector2D;
+
+    fn mul(self, factor: Fixed) -> Vector2D {
+        Vector2D::new(self.x * factor, self.y * factor)
+    }
+}
+
+impl ops::Neg for Vector2D {
+    type Output = Vector2D;
+
+    fn neg(self) -> Vector2D {
+        Vector2D::new(-self.x, -self.y)
+    }
+}
+
+impl ops::AddAssign for Vector2D {
+    fn add_assign(&mut self, other: Vector2D) {
+        *self = *self + other;
+    }
+}
+
+impl ops::SubAssign for Vector2D {
+    fn sub_assign(&mut self, other: Vector2D) {
+        *self = *self - other;
+    }
+}
+
+#[derive(Default, Hash, Reflect, Component, Serialize, Deserialize)]
+#[reflect(Hash)]
 pub struct Transform2D {
     pub scale: Vector2D,
     pub position: Vector2D,
-    pub rotation: f32,
+    pub rotation: Fixed,
 }
 
 #[allow(dead_code)]
@@ -78,10 +253,30 @@ impl Transform2D {
             ..default()
         }
     }
-    pub fn from_rotation(rotation: f32) -> Self {
+    pub fn from_rotation(rotation: Fixed) -> Self {
         Self {
             rotation,
             ..default()
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn to_i32_rounds_towards_zero() {
+        assert_eq!(Fixed::from_ratio(7, 2).to_i32(), 3);
+        assert_eq!(Fixed::from_ratio(-7, 2).to_i32(), -3);
+        assert_eq!(Fixed::from_int(-4).to_i32(), -4);
+    }
+
+    #[test]
+    fn from_f32_handles_non_finite_values() {
+        assert_eq!(Fixed::from_f32(f32::NAN), Fixed::ZERO);
+        assert_eq!(Fixed::from_f32(f32::INFINITY), Fixed(I32F32::MAX));
+        assert_eq!(Fixed::from_f32(f32::NEG_INFINITY), Fixed(I32F32::MIN));
+        assert_eq!(Fixed::from_f32(1.5), Fixed::from_ratio(3, 2));
+    }
+}
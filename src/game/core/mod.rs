@@ -1,6 +1,15 @@
 pub mod anim;
+pub mod camera;
+pub mod controller;
 pub mod debug;
+pub mod diagnostics;
+pub mod forensics;
 pub mod frame;
 pub mod input;
+pub mod level;
 pub mod maths;
+pub mod network;
 pub mod physics;
+pub mod replay;
+pub mod settings;
+pub mod synctest;
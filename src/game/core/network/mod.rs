@@ -0,0 +1,5 @@
+pub mod snapshot;
+pub mod socket;
+pub mod structs;
+pub mod systems;
+pub mod utilities;
@@ -0,0 +1,111 @@
+use bevy::prelude::*;
+use bevy::reflect::TypeRegistryInternal;
+use bevy_ggrs::Rollback;
+use std::collections::HashMap;
+
+/// Rollback components of a rollback entity.
+struct EntitySnapshot {
+    rollback_id: u32,
+    components: Vec<Box<dyn Reflect>>,
+}
+
+/// Registered rollback resources and components of the rollback entities, saved for GGRS to load them back when it
+/// rolls back to their frame.
+#[derive(Default)]
+pub struct RollbackSnapshot {
+    entities: Vec<EntitySnapshot>,
+    resources: Vec<Box<dyn Reflect>>,
+}
+
+impl RollbackSnapshot {
+    pub fn from_world(world: &mut World, type_registry: &TypeRegistryInternal) -> Self {
+        let mut query = world.query::<(Entity, &Rollback)>();
+        let rollback_entities: Vec<_> = query
+            .iter(world)
+            .map(|(entity, rollback)| (entity, rollback.id()))
+            .collect();
+
+        let entities = rollback_entities
+            .into_iter()
+            .map(|(entity, rollback_id)| EntitySnapshot {
+                rollback_id,
+                components: type_registry
+                    .iter()
+                    .filter_map(|registration| registration.data::<ReflectComponent>())
+                    .filter_map(|reflect_component| reflect_component.reflect(world, entity))
+                    .map(|component| component.clone_value())
+                    .collect(),
+            })
+            .collect();
+        let resources = type_registry
+            .iter()
+            .filter_map(|registration| registration.data::<ReflectResource>())
+            .filter_map(|reflect_resource| reflect_resource.reflect(world))
+            .map(|resource| resource.clone_value())
+            .collect();
+
+        Self {
+            entities,
+            resources,
+        }
+    }
+
+    /// Puts the world back in the saved state, the rollback entities spawned since are despawned and the despawned
+    /// ones are spawned again with their rollback components only.
+    pub fn write_to_world(&self, world: &mut World, type_registry: &TypeRegistryInternal) {
+        let mut query = world.query::<(Entity, &Rollback)>();
+        let mut rollback_entities: HashMap<u32, Entity> = query
+            .iter(world)
+            .map(|(entity, rollback)| (rollback.id(), entity))
+            .collect();
+
+        for entity_snapshot in self.entities.iter() {
+            let entity = match rollback_entities.remove(&entity_snapshot.rollback_id) {
+                Some(entity) => entity,
+                None => world.spawn(Rollback::new(entity_snapshot.rollback_id)).id(),
+            };
+
+            for registration in type_registry.iter() {
+                let reflect_component = match registration.data::<ReflectComponent>() {
+                    Some(reflect_component) => reflect_component,
+                    None => continue,
+                };
+                let saved = entity_snapshot
+                    .components
+                    .iter()
+                    .find(|component| component.type_name() == registration.type_name());
+
+                // removed and inserted again rather than applied, applying a list appends to it
+                if world
+                    .entity(entity)
+                    .contains_type_id(registration.type_id())
+                {
+                    reflect_component.remove(world, entity);
+                }
+                if let Some(component) = saved {
+                    reflect_component.insert(world, entity, &**component);
+                }
+            }
+        }
+
+        for (_, entity) in rollback_entities {
+            world.despawn(entity);
+        }
+
+        for registration in type_registry.iter() {
+            let reflect_resource = match registration.data::<ReflectResource>() {
+                Some(reflect_resource) => reflect_resource,
+                None => continue,
+            };
+            let saved = self
+                .resources
+                .iter()
+                .find(|resource| resource.type_name() == registration.type_name());
+
+            match saved {
+                Some(resource) => reflect_resource.apply_or_insert(world, &**resource),
+                None => reflect_resource.remove(world),
+            }
+        }
+    }
+}
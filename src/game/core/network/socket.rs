@@ -0,0 +1,165 @@
+use ggrs::{Message, NonBlockingSocket};
+use log::info;
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use std::error::Error;
+use std::hash::Hash;
+use std::time::{Duration, Instant};
+use structopt::StructOpt;
+
+/// Command line options of the simulated network, everything is off by default.
+#[allow(dead_code)]
+#[derive(StructOpt)]
+pub struct SimulatedNetworkArgs {
+    /// One way delay added to every outgoing packet.
+    #[structopt(long, default_value = "0")]
+    sim_latency_ms: u64,
+    /// Random delay added or removed around the latency.
+    #[structopt(long, default_value = "0")]
+    sim_jitter_ms: u64,
+    /// Probability of dropping a packet, between 0 and 1.
+    #[structopt(long, default_value = "0")]
+    sim_loss: f64,
+    /// Probability of sending a packet twice, between 0 and 1.
+    #[structopt(long, default_value = "0")]
+    sim_duplication: f64,
+    /// Probability of holding back a packet so it arrives after the next ones, between 0 and 1.
+    #[structopt(long, default_value = "0")]
+    sim_reordering: f64,
+    /// Seed of the packet fate, defaults to the local port so peers behave differently.
+    #[structopt(long)]
+    sim_seed: Option<u64>,
+}
+
+#[allow(dead_code)]
+impl SimulatedNetworkArgs {
+    pub fn is_enabled(&self) -> bool {
+        self.sim_latency_ms > 0
+            || self.sim_jitter_ms > 0
+            || self.sim_loss > 0.0
+            || self.sim_duplication > 0.0
+            || self.sim_reordering > 0.0
+    }
+
+    pub fn wrap<A, S>(
+        &self,
+        socket: S,
+        default_seed: u64,
+    ) -> Result<SimulatedNetworkSocket<A, S>, Box<dyn Error>>
+    where
+        A: Clone + PartialEq + Eq + Hash + Send + Sync,
+        S: NonBlockingSocket<A>,
+    {
+        for (name, probability) in [
+            ("sim_loss", self.sim_loss),
+            ("sim_duplication", self.sim_duplication),
+            ("sim_reordering", self.sim_reordering),
+        ] {
+            if !(0.0..=1.0).contains(&probability) {
+                return Err(format!(
+                    "invalid setting {} = {}, expected a value between 0 and 1",
+                    name, probability
+                )
+                .into());
+            }
+        }
+
+        let seed = self.sim_seed.unwrap_or(default_seed);
+        info!(
+            "simulating network: latency {} ms, jitter {} ms, loss {}, duplication {}, reordering {}, seed {}",
+            self.sim_latency_ms,
+            self.sim_jitter_ms,
+            self.sim_loss,
+            self.sim_duplication,
+            self.sim_reordering,
+            seed
+        );
+
+        Ok(SimulatedNetworkSocket {
+            socket,
+            rng: StdRng::seed_from_u64(seed),
+            latency: Duration::from_millis(self.sim_latency_ms),
+            jitter: Duration::from_millis(self.sim_jitter_ms),
+            loss: self.sim_loss,
+            duplication: self.sim_duplication,
+            reordering: self.sim_reordering,
+            in_flight: Vec::new(),
+        })
+    }
+}
+
+/// Socket holding back, dropping, duplicating and shuffling the outgoing packets of another socket.
+///
+/// Only outgoing packets are affected, when every peer uses it each direction gets its share.
+#[allow(dead_code)]
+pub struct SimulatedNetworkSocket<A, S> {
+    socket: S,
+    rng: StdRng,
+    latency: Duration,
+    jitter: Duration,
+    loss: f64,
+    duplication: f64,
+    reordering: f64,
+    in_flight: Vec<(Instant, A, Message)>,
+}
+
+#[allow(dead_code)]
+impl<A, S> SimulatedNetworkSocket<A, S>
+where
+    A: Clone + PartialEq + Eq + Hash + Send + Sync,
+    S: NonBlockingSocket<A>,
+{
+    fn delay(&mut self) -> Duration {
+        let jitter = self.jitter.as_secs_f64() * self.rng.gen_range(-1.0..=1.0);
+        let mut delay = (self.latency.as_secs_f64() + jitter).max(0.0);
+
+        // a late packet is overtaken by the ones sent right after it
+        if self.rng.gen_bool(self.reordering) {
+            delay += (self.latency + self.jitter).as_secs_f64().max(0.05);
+        }
+
+        Duration::from_secs_f64(delay)
+    }
+
+    fn flush(&mut self) {
+        let now = Instant::now();
+
+        let (mut due, in_flight): (Vec<_>, Vec<_>) = std::mem::take(&mut self.in_flight)
+            .into_iter()
+            .partition(|(deliver_at, _, _)| *deliver_at <= now);
+        self.in_flight = in_flight;
+
+        due.sort_by_key(|(deliver_at, _, _)| *deliver_at);
+        for (_, addr, msg) in due {
+            self.socket.send_to(&msg, &addr);
+        }
+    }
+}
+
+impl<A, S> NonBlockingSocket<A> for SimulatedNetworkSocket<A, S>
+where
+    A: Clone + PartialEq + Eq + Hash + Send + Sync,
+    S: NonBlockingSocket<A>,
+{
+    fn send_to(&mut self, msg: &Message, addr: &A) {
+        if !self.rng.gen_bool(self.loss) {
+            let copies = if self.rng.gen_bool(self.duplication) {
+                2
+            } else {
+                1
+            };
+            for _ in 0..copies {
+                let deliver_at = Instant::now() + self.delay();
+                self.in_flight.push((deliver_at, addr.clone(), msg.clone()));
+            }
+        }
+
+        self.flush();
+    }
+
+    fn receive_all_messages(&mut self) -> Vec<(A, Message)> {
+        self.flush();
+
+        self.socket.receive_all_messages()
+    }
+}
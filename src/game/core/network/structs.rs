@@ -0,0 +1,235 @@
+use bevy::prelude::*;
+use bevy::reflect::{FromType, GetTypeRegistration, TypeRegistryInternal};
+use bevy_ggrs::Session;
+use ggrs::{GGRSError, GGRSRequest, PlayerHandle, SessionState};
+use log::{info, warn};
+use std::any::TypeId;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+use crate::game::core::input::structs::{BoxInput, FrameInputsRes};
+use crate::game::core::network::snapshot::RollbackSnapshot;
+use crate::game::core::synctest::utilities::world_checksum;
+use crate::game::GameConfig;
+
+/// Transparency of the frog of a disconnected player.
+pub const DISCONNECTED_PLAYER_ALPHA: f32 = 0.3;
+
+/// Non rollback resource tracking the connection with the other peers, fed by the GGRS events.
+#[derive(Default, Resource)]
+pub struct NetworkStatusRes {
+    /// Synchronization round trips done and needed per peer.
+    pub synchronizing: HashMap<SocketAddr, (u32, u32)>,
+    /// Peers that stopped answering, with the instant they will be disconnected.
+    pub interrupted: HashMap<SocketAddr, Instant>,
+    pub disconnected: HashSet<PlayerHandle>,
+    /// Frames GGRS recommended to wait for the other peers that are still to be skipped.
+    pub recommended_wait_frames: u32,
+}
+
+#[derive(Component)]
+pub struct NetworkStatusText;
+
+/// Stage running the rollback schedule for the GGRS session at the simulation rate, in place of the bevy_ggrs one.
+///
+/// A wait recommendation of GGRS lets the next update periods pass without simulating a frame, the
+/// remote peers are still polled meanwhile.
+pub struct RollbackStage {
+    schedule: Schedule,
+    type_registry: TypeRegistryInternal,
+    input_system: Box<dyn System<In = PlayerHandle, Out = BoxInput>>,
+    snapshots: Vec<RollbackSnapshot>,
+    update_frequency: usize,
+    frame: i32,
+    accumulator: f64,
+    /// Set when this peer is ahead of the others, it then simulates a bit slower to let them catch up.
+    run_slow: bool,
+}
+
+impl RollbackStage {
+    pub fn new<Params>(
+        world: &mut World,
+        schedule: Schedule,
+        update_frequency: usize,
+        input_system: impl IntoSystem<PlayerHandle, BoxInput, Params>,
+    ) -> Self {
+        let mut input_system = Box::new(IntoSystem::into_system(input_system));
+        input_system.initialize(world);
+
+        Self {
+            schedule,
+            type_registry: TypeRegistryInternal::empty(),
+            input_system,
+            snapshots: Vec::new(),
+            update_frequency,
+            frame: 0,
+            accumulator: 0.0,
+            run_slow: false,
+        }
+    }
+
+    pub fn register_rollback_resource<R: GetTypeRegistration + Reflect + Resource + FromWorld>(
+        mut self,
+    ) -> Self {
+        self.type_registry.register::<R>();
+        self.type_registry
+            .get_mut(TypeId::of::<R>())
+            .unwrap()
+            .insert(<ReflectResource as FromType<R>>::from_type());
+        self
+    }
+
+    pub fn register_rollback_component<C: GetTypeRegistration + Reflect + Component + FromWorld>(
+        mut self,
+    ) -> Self {
+        self.type_registry.register::<C>();
+        self.type_registry
+            .get_mut(TypeId::of::<C>())
+            .unwrap()
+            .insert(<ReflectComponent as FromType<C>>::from_type());
+        self
+    }
+
+    fn run_synctest(&mut self, world: &mut World) {
+        let num_players = match world.resource::<Session<GameConfig>>() {
+            Session::SyncTestSession(s) => s.num_players(),
+            _ => unreachable!(),
+        };
+        let inputs: Vec<_> = (0..num_players)
+            .map(|handle| self.input_system.run(handle, world))
+            .collect();
+
+        let mut session = world.resource_mut::<Session<GameConfig>>();
+        let s = match &mut *session {
+            Session::SyncTestSession(s) => s,
+            _ => unreachable!(),
+        };
+        self.snapshots
+            .resize_with(s.max_prediction(), RollbackSnapshot::default);
+        for (handle, input) in inputs.into_iter().enumerate() {
+            s.add_local_input(handle, input)
+                .expect("every handle of a synctest session is local");
+        }
+
+        match s.advance_frame() {
+            Ok(requests) => self.handle_requests(requests, world),
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    fn run_p2p(&mut self, world: &mut World) {
+        let mut network_status = world.resource_mut::<NetworkStatusRes>();
+        if network_status.recommended_wait_frames > 0 {
+            network_status.recommended_wait_frames -= 1;
+            return;
+        }
+
+        let (running, local_handles) = match world.resource::<Session<GameConfig>>() {
+            Session::P2PSession(s) => {
+                self.run_slow = s.frames_ahead() > 0;
+                (
+                    s.current_state() == SessionState::Running,
+                    s.local_player_handles(),
+                )
+            }
+            _ => unreachable!(),
+        };
+        if !running {
+            return;
+        }
+        let inputs: Vec<_> = local_handles
+            .iter()
+            .map(|&handle| self.input_system.run(handle, world))
+            .collect();
+
+        let mut session = world.resource_mut::<Session<GameConfig>>();
+        let s = match &mut *session {
+            Session::P2PSession(s) => s,
+            _ => unreachable!(),
+        };
+        self.snapshots
+            .resize_with(s.max_prediction(), RollbackSnapshot::default);
+        for (handle, input) in local_handles.into_iter().zip(inputs) {
+            s.add_local_input(handle, input)
+                .expect("local handles are local players");
+        }
+
+        match s.advance_frame() {
+            Ok(requests) => self.handle_requests(requests, world),
+            Err(GGRSError::PredictionThreshold) => {
+                info!("prediction threshold reached, waiting for the remote inputs")
+            }
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    fn run_spectator(&mut self, world: &mut World) {
+        let mut session = world.resource_mut::<Session<GameConfig>>();
+        let s = match &mut *session {
+            Session::SpectatorSession(s) => s,
+            _ => unreachable!(),
+        };
+        if s.current_state() != SessionState::Running {
+            return;
+        }
+
+        match s.advance_frame() {
+            Ok(requests) => self.handle_requests(requests, world),
+            Err(GGRSError::PredictionThreshold) => info!("waiting for the inputs of the host"),
+            Err(e) => warn!("{}", e),
+        }
+    }
+
+    fn handle_requests(&mut self, requests: Vec<GGRSRequest<GameConfig>>, world: &mut World) {
+        for request in requests {
+            match request {
+                GGRSRequest::SaveGameState { cell, frame } => {
+                    assert_eq!(self.frame, frame);
+                    let snapshot = RollbackSnapshot::from_world(world, &self.type_registry);
+                    // the snapshots stay here, GGRS only keeps the checksum compared between peers
+                    cell.save(frame, None, Some(world_checksum(world) as u128));
+                    let index = frame as usize % self.snapshots.len();
+                    self.snapshots[index] = snapshot;
+                }
+                GGRSRequest::LoadGameState { frame, .. } => {
+                    let index = frame as usize % self.snapshots.len();
+                    self.snapshots[index].write_to_world(world, &self.type_registry);
+                    self.frame = frame;
+                }
+                GGRSRequest::AdvanceFrame { inputs } => {
+                    world.resource_mut::<FrameInputsRes>().0 = inputs;
+                    self.schedule.run_once(world);
+                    self.frame += 1;
+                }
+            }
+        }
+    }
+}
+
+impl Stage for RollbackStage {
+    fn run(&mut self, world: &mut World) {
+        let mut frame_secs = 1.0 / self.update_frequency as f64;
+        if self.run_slow {
+            frame_secs *= 1.1;
+        }
+        self.accumulator += world.resource::<Time>().delta_seconds_f64();
+
+        // the remote peers are answered on every update, even the ones simulating no frame
+        match &mut *world.resource_mut::<Session<GameConfig>>() {
+            Session::P2PSession(s) => s.poll_remote_clients(),
+            Session::SpectatorSession(s) => s.poll_remote_clients(),
+            Session::SyncTestSession(_) => (),
+        }
+
+        while self.accumulator >= frame_secs {
+            self.accumulator -= frame_secs;
+
+            match world.resource::<Session<GameConfig>>() {
+                Session::SyncTestSession(_) => self.run_synctest(world),
+                Session::P2PSession(_) => self.run_p2p(world),
+                Session::SpectatorSession(_) => self.run_spectator(world),
+            }
+        }
+    }
+}
@@ -0,0 +1,156 @@
+use bevy::prelude::*;
+use bevy_ggrs::Session;
+use ggrs::{GGRSEvent, SessionState};
+use log::{error, info, warn};
+use std::fmt::Write;
+use std::time::{Duration, Instant};
+
+use crate::game::core::forensics::structs::DesyncDetectedEvent;
+use crate::game::core::network::structs::{
+    NetworkStatusRes, NetworkStatusText, DISCONNECTED_PLAYER_ALPHA,
+};
+use crate::game::player::structs::Player2D;
+use crate::game::GameConfig;
+
+pub fn network_events_system(
+    mut session: ResMut<Session<GameConfig>>,
+    mut network_status: ResMut<NetworkStatusRes>,
+    mut desync_detected_events: EventWriter<DesyncDetectedEvent>,
+) {
+    let events: Vec<GGRSEvent<GameConfig>> = match &mut *session {
+        Session::P2PSession(s) => s.events().collect(),
+        Session::SpectatorSession(s) => s.events().collect(),
+        Session::SyncTestSession(_) => Vec::new(),
+    };
+
+    for event in events {
+        match event {
+            GGRSEvent::Synchronizing { addr, total, count } => {
+                info!("synchronizing with {}: {}/{}", addr, count, total);
+                network_status.synchronizing.insert(addr, (count, total));
+            }
+            GGRSEvent::Synchronized { addr } => {
+                info!("synchronized with {}", addr);
+                network_status.synchronizing.remove(&addr);
+            }
+            GGRSEvent::NetworkInterrupted {
+                addr,
+                disconnect_timeout,
+            } => {
+                warn!(
+                    "connection interrupted with {}, disconnecting in {} ms",
+                    addr, disconnect_timeout
+                );
+                network_status.interrupted.insert(
+                    addr,
+                    Instant::now() + Duration::from_millis(disconnect_timeout as u64),
+                );
+            }
+            GGRSEvent::NetworkResumed { addr } => {
+                info!("connection resumed with {}", addr);
+                network_status.interrupted.remove(&addr);
+            }
+            GGRSEvent::Disconnected { addr } => {
+                network_status.interrupted.remove(&addr);
+
+                // spectators only know the host, which has no player handle of its own
+                let handles = match &*session {
+                    Session::P2PSession(s) => s.handles_by_address(addr),
+                    _ => Vec::new(),
+                };
+                warn!("disconnected from {}, player handles {:?}", addr, handles);
+                network_status.disconnected.extend(handles);
+            }
+            GGRSEvent::WaitRecommendation { skip_frames } => {
+                // waited out by the rollback stage, a newer recommendation replaces the pending one
+                info!("wait recommendation: {} frames ahead", skip_frames);
+                network_status.recommended_wait_frames =
+                    network_status.recommended_wait_frames.max(skip_frames);
+            }
+            GGRSEvent::DesyncDetected {
+                frame,
+                local_checksum,
+                remote_checksum,
+                addr,
+            } => {
+                error!(
+                    "desync detected at frame {} with {}: local checksum {:x}, remote checksum {:x}",
+                    frame, addr, local_checksum, remote_checksum
+                );
+                desync_detected_events.send(DesyncDetectedEvent {
+                    frame: frame as u32,
+                });
+            }
+        }
+    }
+}
+
+pub fn startup_network_status_system(asset_server: Res<AssetServer>, mut commands: Commands) {
+    let font_handle: Handle<Font> = asset_server.load("fonts/Pixellari.ttf");
+
+    commands.spawn((
+        TextBundle::from_section(
+            "",
+            TextStyle {
+                font: font_handle,
+                color: Color::ORANGE,
+                font_size: 24.0,
+            },
+        )
+        .with_text_alignment(TextAlignment::CENTER)
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            position: UiRect {
+                top: Val::Px(40.0),
+                left: Val::Percent(30.0),
+                ..default()
+            },
+            ..default()
+        }),
+        NetworkStatusText,
+    ));
+}
+
+pub fn network_status_system(
+    session: Res<Session<GameConfig>>,
+    network_status: Res<NetworkStatusRes>,
+    //
+    mut query_text: Query<&mut Text, With<NetworkStatusText>>,
+    mut query_sprite: Query<(&Player2D, &mut TextureAtlasSprite)>,
+) {
+    let mut text = String::new();
+
+    let running = match &*session {
+        Session::P2PSession(s) => s.current_state() == SessionState::Running,
+        Session::SpectatorSession(s) => s.current_state() == SessionState::Running,
+        Session::SyncTestSession(_) => true,
+    };
+    if !running {
+        writeln!(text, "synchronizing...").unwrap();
+        for (addr, (count, total)) in network_status.synchronizing.iter() {
+            writeln!(text, "{}: {}/{}", addr, count, total).unwrap();
+        }
+    }
+
+    let now = Instant::now();
+    for (addr, disconnect_at) in network_status.interrupted.iter() {
+        writeln!(
+            text,
+            "connection interrupted with {}\ndisconnecting in {:.1} s",
+            addr,
+            disconnect_at.saturating_duration_since(now).as_secs_f32()
+        )
+        .unwrap();
+    }
+
+    for mut status_text in query_text.iter_mut() {
+        status_text.sections[0].value = text.clone();
+    }
+
+    // disconnected players receive no more inputs and stand still, show them as ghosts
+    for (player, mut sprite) in query_sprite.iter_mut() {
+        if network_status.disconnected.contains(&player.handle) {
+            sprite.color.set_a(DISCONNECTED_PLAYER_ALPHA);
+        }
+    }
+}
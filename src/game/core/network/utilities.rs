@@ -0,0 +1,210 @@
+use ggrs::PlayerHandle;
+use log::info;
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream, UdpSocket};
+use std::time::{Duration, Instant};
+
+const HANDSHAKE_MAGIC: &[u8; 4] = b"FRHS";
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(60);
+const HANDSHAKE_RESEND: Duration = Duration::from_millis(100);
+/// Time the handshake keeps answering once it is done, for the peers which missed its last answer.
+const HANDSHAKE_LINGER: Duration = Duration::from_secs(1);
+
+/// Joins a room on the lobby server and waits for it to fill up.
+///
+/// The client sends `JOIN <room> <room size> <game port> <level checksum> <fps> [<advertised ip>]`, the server answers
+/// once the room is full with `START <player handle> <player 0 address> <player 1 address> ...` or `ERROR <reason>`.
+/// Without an advertised ip the other players reach the game on the address it reached the lobby from.
+#[allow(dead_code)]
+pub fn join_lobby(
+    lobby_addr: SocketAddr,
+    room: &str,
+    room_size: usize,
+    port: u16,
+    level_checksum: u64,
+    fps: usize,
+    advertised_ip: Option<IpAddr>,
+) -> Result<(PlayerHandle, Vec<SocketAddr>), Box<dyn Error>> {
+    let mut stream = TcpStream::connect(lobby_addr)?;
+    let mut request = format!(
+        "JOIN {} {} {} {:016x} {}",
+        room, room_size, port, level_checksum, fps
+    );
+    if let Some(advertised_ip) = advertised_ip {
+        request = format!("{} {}", request, advertised_ip);
+    }
+    writeln!(stream, "{}", request)?;
+    info!(
+        "joined room {} on lobby {}, waiting for {} players",
+        room, lobby_addr, room_size
+    );
+
+    let mut line = String::new();
+    BufReader::new(stream).read_line(&mut line)?;
+
+    let mut tokens = line.split_whitespace();
+    match tokens.next() {
+        Some("START") => {
+            let handle: PlayerHandle = tokens.next().ok_or("missing player handle")?.parse()?;
+            let addrs = tokens
+                .map(|addr| addr.parse())
+                .collect::<Result<Vec<SocketAddr>, _>>()?;
+            if handle >= addrs.len() || addrs.len() != room_size {
+                return Err(format!("invalid lobby answer: {}", line.trim()).into());
+            }
+
+            Ok((handle, addrs))
+        }
+        Some("ERROR") => {
+            Err(format!("lobby error: {}", tokens.collect::<Vec<_>>().join(" ")).into())
+        }
+        _ => Err(format!("invalid lobby answer: {}", line.trim()).into()),
+    }
+}
+
+/// Checks that every peer of a session simulates the same level at the same frame rate, before the game port is
+/// handed to GGRS.
+///
+/// Each peer sends `<magic> <level checksum (u64)> <fps (u16)> <received yours (u8)> <answer (u8)>` to the others
+/// until it got their message and they got its own. Every message but the answers is answered, the handshake keeps
+/// answering for a moment once done since its last answer may be lost.
+#[allow(dead_code)]
+pub fn exchange_session_checks(
+    port: u16,
+    peers: &[SocketAddr],
+    level_checksum: u64,
+    fps: usize,
+) -> Result<(), Box<dyn Error>> {
+    if peers.is_empty() {
+        return Ok(());
+    }
+
+    let socket = UdpSocket::bind(("0.0.0.0", port))?;
+    socket.set_read_timeout(Some(HANDSHAKE_RESEND))?;
+    let message = |received: bool, answer: bool| {
+        let mut message = HANDSHAKE_MAGIC.to_vec();
+        message.extend_from_slice(&level_checksum.to_le_bytes());
+        message.extend_from_slice(&(fps as u16).to_le_bytes());
+        message.push(received as u8);
+        message.push(answer as u8);
+        message
+    };
+
+    let mut received = HashSet::new();
+    let mut acknowledged = HashSet::new();
+    let started_at = Instant::now();
+    let mut done_at = None;
+    let mut next_send_at = started_at;
+    eprintln!("checking the level and the frame rate of {:?}", peers);
+
+    loop {
+        let now = Instant::now();
+        match done_at {
+            Some(done_at) if now - done_at >= HANDSHAKE_LINGER => return Ok(()),
+            None if now - started_at >= HANDSHAKE_TIMEOUT => {
+                let missing: Vec<_> = peers
+                    .iter()
+                    .filter(|peer| !received.contains(*peer) || !acknowledged.contains(*peer))
+                    .collect();
+                return Err(format!("no answer from {:?}", missing).into());
+            }
+            _ => {}
+        }
+
+        if now >= next_send_at {
+            for peer in peers.iter().filter(|peer| !acknowledged.contains(*peer)) {
+                socket.send_to(&message(received.contains(peer), false), peer)?;
+            }
+            next_send_at = now + HANDSHAKE_RESEND;
+        }
+
+        let mut buffer = [0; 16];
+        let (size, peer) = match socket.recv_from(&mut buffer) {
+            Ok(datagram) => datagram,
+            Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => continue,
+            // the port of a peer not started yet is unreachable, it is asked again later
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => continue,
+            Err(e) => return Err(e.into()),
+        };
+        if size != 16 || &buffer[..4] != HANDSHAKE_MAGIC || !peers.contains(&peer) {
+            continue;
+        }
+
+        let peer_level_checksum = u64::from_le_bytes(buffer[4..12].try_into().unwrap());
+        let peer_fps = u16::from_le_bytes(buffer[12..14].try_into().unwrap()) as usize;
+        if peer_level_checksum != level_checksum || peer_fps != fps {
+            // the peer may not have our message yet, let it fail too instead of waiting for us
+            socket.send_to(&message(true, true), peer)?;
+        }
+        if peer_level_checksum != level_checksum {
+            return Err(format!(
+                "{} plays another level, checksum {:016x} instead of {:016x}",
+                peer, peer_level_checksum, level_checksum
+            )
+            .into());
+        }
+        if peer_fps != fps {
+            return Err(format!(
+                "{} simulates {} frames per second instead of {}",
+                peer, peer_fps, fps
+            )
+            .into());
+        }
+
+        received.insert(peer);
+        if buffer[14] != 0 {
+            acknowledged.insert(peer);
+        }
+        if buffer[15] == 0 {
+            socket.send_to(&message(true, true), peer)?;
+        }
+
+        if done_at.is_none() && received.len() == peers.len() && acknowledged.len() == peers.len() {
+            done_at = Some(Instant::now());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    fn handshake(
+        port: u16,
+        peer_port: u16,
+        level_checksum: u64,
+        fps: usize,
+    ) -> thread::JoinHandle<Result<(), String>> {
+        thread::spawn(move || {
+            let peer = SocketAddr::from(([127, 0, 0, 1], peer_port));
+            exchange_session_checks(port, &[peer], level_checksum, fps).map_err(|e| e.to_string())
+        })
+    }
+
+    #[test]
+    fn peers_of_the_same_session_pass_the_handshake() {
+        let first = handshake(47811, 47812, 0x1234, 60);
+        let second = handshake(47812, 47811, 0x1234, 60);
+
+        assert_eq!(first.join().unwrap(), Ok(()));
+        assert_eq!(second.join().unwrap(), Ok(()));
+    }
+
+    #[test]
+    fn peers_on_another_level_or_frame_rate_are_refused() {
+        let first = handshake(47813, 47814, 0x1234, 60);
+        let second = handshake(47814, 47813, 0x5678, 60);
+        let err = first.join().unwrap().unwrap_err();
+        assert!(err.contains("another level"), "{}", err);
+        assert!(second.join().unwrap().is_err());
+
+        let first = handshake(47815, 47816, 0x1234, 60);
+        let second = handshake(47816, 47815, 0x1234, 30);
+        let err = first.join().unwrap().unwrap_err();
+        assert!(err.contains("30 frames per second"), "{}", err);
+        assert!(second.join().unwrap().is_err());
+    }
+}
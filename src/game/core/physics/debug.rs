@@ -0,0 +1,261 @@
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+use rapier2d::prelude::*;
+
+use crate::game::core::physics::range::scale_physics;
+use crate::game::core::physics::structs::{ColliderSetRes, NarrowPhaseRes, RigidBodySetRes};
+use crate::game::core::physics::systems::{
+    ONE_WAY_PHYSICS_GROUP, PLAYER_PHYSICS_GROUP, SOLID_PHYSICS_GROUP,
+};
+
+const PHYSICS_DEBUG_Z: f32 = 800.0;
+const PHYSICS_DEBUG_CIRCLE_SUBDIVISIONS: u32 = 16;
+/// Half size of the crosses marking the contact points, in pixels.
+const PHYSICS_DEBUG_CONTACT_SIZE: f32 = 3.0;
+
+/// Debug drawing of the physics world, it reads the physics state without ever modifying it.
+/// F4 toggles the whole drawing, F5 the AABBs and F6 the contact points.
+#[derive(Resource)]
+pub struct PhysicsDebugRes {
+    pub visible: bool,
+    pub show_aabbs: bool,
+    pub show_contacts: bool,
+}
+
+impl Default for PhysicsDebugRes {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            show_aabbs: true,
+            show_contacts: true,
+        }
+    }
+}
+
+/// Every layer is a single path redrawn each frame in a single color.
+#[derive(Clone, Copy, PartialEq, Eq, Component)]
+pub enum PhysicsDebugLayer {
+    Solid,
+    Player,
+    OneWay,
+    Other,
+    Sleeping,
+    Aabbs,
+    Contacts,
+}
+
+impl PhysicsDebugLayer {
+    pub const ALL: [PhysicsDebugLayer; 7] = [
+        PhysicsDebugLayer::Solid,
+        PhysicsDebugLayer::Player,
+        PhysicsDebugLayer::OneWay,
+        PhysicsDebugLayer::Other,
+        PhysicsDebugLayer::Sleeping,
+        PhysicsDebugLayer::Aabbs,
+        PhysicsDebugLayer::Contacts,
+    ];
+
+    fn color(self) -> Color {
+        match self {
+            PhysicsDebugLayer::Solid => Color::GREEN,
+            PhysicsDebugLayer::Player => Color::YELLOW,
+            PhysicsDebugLayer::OneWay => Color::ORANGE,
+            PhysicsDebugLayer::Other => Color::FUCHSIA,
+            PhysicsDebugLayer::Sleeping => Color::GRAY,
+            PhysicsDebugLayer::Aabbs => Color::CYAN,
+            PhysicsDebugLayer::Contacts => Color::RED,
+        }
+    }
+
+    /// Layer of a collider, picked from the first of its collision groups.
+    fn of_collider(collider: &Collider, sleeping: bool) -> Self {
+        let memberships = collider.collision_groups().memberships;
+
+        if sleeping {
+            PhysicsDebugLayer::Sleeping
+        } else if memberships.contains(SOLID_PHYSICS_GROUP) {
+            PhysicsDebugLayer::Solid
+        } else if memberships.contains(PLAYER_PHYSICS_GROUP) {
+            PhysicsDebugLayer::Player
+        } else if memberships.contains(ONE_WAY_PHYSICS_GROUP) {
+            PhysicsDebugLayer::OneWay
+        } else {
+            PhysicsDebugLayer::Other
+        }
+    }
+}
+
+pub fn startup_physics_debug_system(mut commands: Commands) {
+    for layer in PhysicsDebugLayer::ALL {
+        commands.spawn((
+            GeometryBuilder::new().build(
+                DrawMode::Stroke(StrokeMode::new(layer.color(), 1.0)),
+                Transform::from_translation(Vec3::new(0.0, 0.0, PHYSICS_DEBUG_Z)),
+            ),
+            layer,
+        ));
+    }
+}
+
+pub fn physics_debug_system(
+    keyboard_input: Res<Input<KeyCode>>,
+    collider_set: Res<ColliderSetRes>,
+    narrow_phase: Res<NarrowPhaseRes>,
+    rigid_body_set: Res<RigidBodySetRes>,
+    //
+    mut physics_debug: ResMut<PhysicsDebugRes>,
+    mut query: Query<(&mut Path, &mut Visibility, &PhysicsDebugLayer)>,
+) {
+    if keyboard_input.just_pressed(KeyCode::F4) {
+        physics_debug.visible = !physics_debug.visible;
+    }
+    if keyboard_input.just_pressed(KeyCode::F5) {
+        physics_debug.show_aabbs = !physics_debug.show_aabbs;
+    }
+    if keyboard_input.just_pressed(KeyCode::F6) {
+        physics_debug.show_contacts = !physics_debug.show_contacts;
+    }
+
+    for (_, mut visibility, layer) in query.iter_mut() {
+        visibility.is_visible = physics_debug.visible
+            && match layer {
+                PhysicsDebugLayer::Aabbs => physics_debug.show_aabbs,
+                PhysicsDebugLayer::Contacts => physics_debug.show_contacts,
+                _ => true,
+            };
+    }
+    if !physics_debug.visible {
+        return;
+    }
+
+    // one path builder per layer, indexed by the layer
+    let mut builders: Vec<PathBuilder> = PhysicsDebugLayer::ALL
+        .iter()
+        .map(|_| PathBuilder::new())
+        .collect();
+
+    for (_, collider) in collider_set.iter() {
+        let sleeping = collider
+            .parent()
+            .and_then(|rigid_body_handle| rigid_body_set.get(rigid_body_handle))
+            .is_some_and(|rigid_body| rigid_body.is_sleeping());
+
+        draw_shape(
+            &mut builders[PhysicsDebugLayer::of_collider(collider, sleeping) as usize],
+            collider.position(),
+            collider.shape(),
+        );
+
+        let aabb = collider.compute_aabb();
+        draw_polygon(
+            &mut builders[PhysicsDebugLayer::Aabbs as usize],
+            &[
+                aabb.mins,
+                point![aabb.maxs.x, aabb.mins.y],
+                aabb.maxs,
+                point![aabb.mins.x, aabb.maxs.y],
+            ],
+            true,
+        );
+    }
+
+    let contacts = &mut builders[PhysicsDebugLayer::Contacts as usize];
+    for contact_pair in narrow_phase.contact_pairs() {
+        for manifold in contact_pair.manifolds.iter() {
+            for solver_contact in manifold.data.solver_contacts.iter() {
+                let center = to_screen(&solver_contact.point);
+                for offset in [
+                    Vec2::new(PHYSICS_DEBUG_CONTACT_SIZE, PHYSICS_DEBUG_CONTACT_SIZE),
+                    Vec2::new(PHYSICS_DEBUG_CONTACT_SIZE, -PHYSICS_DEBUG_CONTACT_SIZE),
+                ] {
+                    contacts.move_to(center - offset);
+                    contacts.line_to(center + offset);
+                }
+            }
+        }
+    }
+
+    for (mut path, _, layer) in query.iter_mut() {
+        *path = std::mem::replace(&mut builders[*layer as usize], PathBuilder::new()).build();
+    }
+}
+
+/// Draws the outline of a shape placed at `position`, compound shapes are drawn part by part.
+fn draw_shape(builder: &mut PathBuilder, position: &Isometry<Real>, shape: &dyn Shape) {
+    let polygon = |builder: &mut PathBuilder, points: &[Point<Real>]| {
+        let points: Vec<_> = points.iter().map(|point| position * point).collect();
+        draw_polygon(builder, &points, true);
+    };
+
+    match shape.shape_type() {
+        ShapeType::Ball => polygon(
+            builder,
+            &shape
+                .as_ball()
+                .unwrap()
+                .to_polyline(PHYSICS_DEBUG_CIRCLE_SUBDIVISIONS),
+        ),
+        ShapeType::Cuboid => polygon(builder, &shape.as_cuboid().unwrap().to_polyline()),
+        ShapeType::Capsule => polygon(
+            builder,
+            &shape
+                .as_capsule()
+                .unwrap()
+                .to_polyline(PHYSICS_DEBUG_CIRCLE_SUBDIVISIONS),
+        ),
+        ShapeType::ConvexPolygon => polygon(builder, shape.as_convex_polygon().unwrap().points()),
+        ShapeType::Triangle => {
+            let triangle = shape.as_triangle().unwrap();
+            polygon(builder, &[triangle.a, triangle.b, triangle.c]);
+        }
+        ShapeType::Segment => {
+            let segment = shape.as_segment().unwrap();
+            draw_polygon(
+                builder,
+                &[position * segment.a, position * segment.b],
+                false,
+            );
+        }
+        ShapeType::Polyline => {
+            for segment in shape.as_polyline().unwrap().segments() {
+                draw_polygon(
+                    builder,
+                    &[position * segment.a, position * segment.b],
+                    false,
+                );
+            }
+        }
+        ShapeType::TriMesh => {
+            for triangle in shape.as_trimesh().unwrap().triangles() {
+                polygon(builder, &[triangle.a, triangle.b, triangle.c]);
+            }
+        }
+        ShapeType::Compound => {
+            for (part_position, part_shape) in shape.as_compound().unwrap().shapes() {
+                draw_shape(builder, &(position * part_position), &**part_shape);
+            }
+        }
+        _ => (),
+    }
+}
+
+fn draw_polygon(builder: &mut PathBuilder, points: &[Point<Real>], closed: bool) {
+    let mut points = points.iter().map(to_screen);
+
+    if let Some(first) = points.next() {
+        builder.move_to(first);
+        for point in points {
+            builder.line_to(point);
+        }
+        if closed {
+            builder.close();
+        }
+    }
+}
+
+fn to_screen(point: &Point<Real>) -> Vec2 {
+    Vec2::new(
+        scale_physics(point.x).to_f32(),
+        scale_physics(point.y).to_f32(),
+    )
+}
@@ -0,0 +1,130 @@
+use bevy::prelude::*;
+use rapier2d::prelude::*;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+use crate::game::core::physics::structs::{PhysicsEvent2D, RigidBodyRemovedEntitiesRes};
+
+/// Rapier events as reported during a step, before their colliders are mapped to entities.
+enum RawPhysicsEvent {
+    Collision(CollisionEvent),
+    ContactForce {
+        collider1: ColliderHandle,
+        collider2: ColliderHandle,
+        total_force_magnitude: Real,
+    },
+}
+
+/// Collects the events of a single physics step, in the order rapier reports them.
+#[derive(Default)]
+pub struct PhysicsEventCollector {
+    events: Mutex<Vec<RawPhysicsEvent>>,
+}
+
+impl EventHandler for PhysicsEventCollector {
+    fn handle_collision_event(
+        &self,
+        _bodies: &RigidBodySet,
+        _colliders: &ColliderSet,
+        event: CollisionEvent,
+        _contact_pair: Option<&ContactPair>,
+    ) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(RawPhysicsEvent::Collision(event));
+    }
+
+    fn handle_contact_force_event(
+        &self,
+        _dt: Real,
+        _bodies: &RigidBodySet,
+        _colliders: &ColliderSet,
+        contact_pair: &ContactPair,
+        total_force_magnitude: Real,
+    ) {
+        self.events
+            .lock()
+            .unwrap()
+            .push(RawPhysicsEvent::ContactForce {
+                collider1: contact_pair.collider1,
+                collider2: contact_pair.collider2,
+                total_force_magnitude,
+            });
+    }
+}
+
+impl PhysicsEventCollector {
+    /// Maps the collected events to the entities owning the colliders, events of colliders without an entity are dropped.
+    pub fn into_events(
+        self,
+        collider_set: &ColliderSet,
+        rigid_body_entities: &RigidBodyRemovedEntitiesRes,
+    ) -> Vec<PhysicsEvent2D> {
+        let entities: HashMap<RigidBodyHandle, Entity> = rigid_body_entities
+            .iter()
+            .map(|(&entity, &rigid_body_handle)| (rigid_body_handle, entity))
+            .collect();
+        let body = |collider_handle: ColliderHandle| {
+            collider_set
+                .get(collider_handle)
+                .and_then(|collider| collider.parent())
+                .and_then(|rigid_body_handle| {
+                    entities
+                        .get(&rigid_body_handle)
+                        .map(|&entity| (entity, rigid_body_handle))
+                })
+        };
+        let bodies = |collider1, collider2| Some((body(collider1)?, body(collider2)?));
+
+        self.events
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .filter_map(|event| match event {
+                RawPhysicsEvent::Collision(CollisionEvent::Started(
+                    collider1,
+                    collider2,
+                    flags,
+                )) => {
+                    let ((entity1, rigid_body1), (entity2, rigid_body2)) =
+                        bodies(collider1, collider2)?;
+                    Some(PhysicsEvent2D::CollisionStarted {
+                        entity1,
+                        entity2,
+                        rigid_bodies: (rigid_body1, rigid_body2),
+                        sensor: flags.contains(CollisionEventFlags::SENSOR),
+                    })
+                }
+                RawPhysicsEvent::Collision(CollisionEvent::Stopped(
+                    collider1,
+                    collider2,
+                    flags,
+                )) => {
+                    let ((entity1, rigid_body1), (entity2, rigid_body2)) =
+                        bodies(collider1, collider2)?;
+                    Some(PhysicsEvent2D::CollisionStopped {
+                        entity1,
+                        entity2,
+                        rigid_bodies: (rigid_body1, rigid_body2),
+                        sensor: flags.contains(CollisionEventFlags::SENSOR),
+                    })
+                }
+                RawPhysicsEvent::ContactForce {
+                    collider1,
+                    collider2,
+                    total_force_magnitude,
+                } => {
+                    let ((entity1, rigid_body1), (entity2, rigid_body2)) =
+                        bodies(collider1, collider2)?;
+                    Some(PhysicsEvent2D::ContactForce {
+                        entity1,
+                        entity2,
+                        rigid_bodies: (rigid_body1, rigid_body2),
+                        total_force_magnitude,
+                    })
+                }
+            })
+            .collect()
+    }
+}
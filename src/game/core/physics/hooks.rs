@@ -0,0 +1,123 @@
+use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
+
+const SURFACE_ONE_WAY: u128 = 1 << 0;
+const SURFACE_CONVEYOR: u128 = 1 << 1;
+const SURFACE_FRICTION: u128 = 1 << 2;
+
+/// Contact behaviour of a collider, stored in its `user_data` so it is rolled back with the `ColliderSet`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PhysicsSurface2D {
+    /// Bodies only collide with the top of the collider, they go up through it.
+    pub one_way: bool,
+    /// Speed the surface carries the bodies at along its top, in meters per second.
+    pub conveyor_speed: Option<f32>,
+    /// Friction used for every contact with the surface, instead of combining the friction of both colliders.
+    pub friction: Option<f32>,
+}
+
+impl PhysicsSurface2D {
+    /// Stores the surface in the collider and enables the hooks it needs.
+    pub fn apply(self, builder: ColliderBuilder) -> ColliderBuilder {
+        let mut active_hooks = ActiveHooks::empty();
+        if self.one_way || self.conveyor_speed.is_some() || self.friction.is_some() {
+            active_hooks |= ActiveHooks::MODIFY_SOLVER_CONTACTS;
+        }
+
+        builder
+            .user_data(self.to_user_data())
+            .active_hooks(active_hooks)
+    }
+
+    // layout: flags in bits 0..3, conveyor speed in bits 32..64, friction in bits 64..96
+    pub fn to_user_data(self) -> u128 {
+        let mut user_data = 0;
+        if self.one_way {
+            user_data |= SURFACE_ONE_WAY;
+        }
+        if let Some(conveyor_speed) = self.conveyor_speed {
+            user_data |= SURFACE_CONVEYOR | (conveyor_speed.to_bits() as u128) << 32;
+        }
+        if let Some(friction) = self.friction {
+            user_data |= SURFACE_FRICTION | (friction.to_bits() as u128) << 64;
+        }
+        user_data
+    }
+
+    pub fn from_user_data(user_data: u128) -> Self {
+        let field =
+            |flag: u128, shift: u32| (user_data & flag != 0).then_some((user_data >> shift) as u32);
+
+        Self {
+            one_way: user_data & SURFACE_ONE_WAY != 0,
+            conveyor_speed: field(SURFACE_CONVEYOR, 32).map(f32::from_bits),
+            friction: field(SURFACE_FRICTION, 64).map(f32::from_bits),
+        }
+    }
+}
+
+/// Physics hooks of the game, they only read the colliders so they are as deterministic as the rollback state.
+pub struct GamePhysicsHooks;
+
+impl GamePhysicsHooks {
+    fn surfaces(
+        colliders: &ColliderSet,
+        collider1: ColliderHandle,
+        collider2: ColliderHandle,
+    ) -> (PhysicsSurface2D, PhysicsSurface2D) {
+        (
+            PhysicsSurface2D::from_user_data(colliders[collider1].user_data),
+            PhysicsSurface2D::from_user_data(colliders[collider2].user_data),
+        )
+    }
+}
+
+impl PhysicsHooks for GamePhysicsHooks {
+    fn modify_solver_contacts(&self, context: &mut ContactModificationContext) {
+        let (surface1, surface2) =
+            Self::surfaces(context.colliders, context.collider1, context.collider2);
+
+        // the contact normal points from the first collider to the second one
+        if surface1.one_way {
+            context.update_as_oneway_platform(&Vector::y(), 0.1);
+        } else if surface2.one_way {
+            context.update_as_oneway_platform(&-Vector::y(), 0.1);
+        }
+
+        // tangent velocities are seen from the first collider, a conveyor second in the pair runs backwards
+        let conveyor_speed = match (surface1.conveyor_speed, surface2.conveyor_speed) {
+            (Some(speed), _) => Some(speed),
+            (None, Some(speed)) => Some(-speed),
+            (None, None) => None,
+        };
+        let friction = surface1.friction.or(surface2.friction);
+
+        for solver_contact in context.solver_contacts.iter_mut() {
+            if let Some(conveyor_speed) = conveyor_speed {
+                solver_contact.tangent_velocity.x = conveyor_speed;
+            }
+            if let Some(friction) = friction {
+                solver_contact.friction = friction;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn surface_round_trips_through_user_data() {
+        let surface = PhysicsSurface2D {
+            one_way: true,
+            conveyor_speed: Some(-2.5),
+            friction: Some(0.25),
+        };
+
+        assert_eq!(
+            PhysicsSurface2D::from_user_data(surface.to_user_data()),
+            surface
+        );
+    }
+}
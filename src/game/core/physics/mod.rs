@@ -1,3 +1,6 @@
+pub mod debug;
+pub mod events;
+pub mod hooks;
 pub mod range;
 pub mod structs;
 pub mod systems;
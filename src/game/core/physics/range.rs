@@ -1,5 +1,9 @@
-pub const PHYSICS_SCALE: f32 = 20.0;
+use crate::game::core::maths::structs::Fixed;
 
-pub fn scale_physics(meters: f32) -> f32 {
-    meters * PHYSICS_SCALE
+/// Pixels per meter.
+pub const PHYSICS_SCALE: Fixed = Fixed::from_int(20);
+
+/// Converts meters coming out of rapier to the pixels of the gameplay state.
+pub fn scale_physics(meters: f32) -> Fixed {
+    Fixed::from_f32(meters) * PHYSICS_SCALE
 }
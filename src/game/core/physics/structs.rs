@@ -1,9 +1,14 @@
+use bevy::ecs::entity::{EntityMap, MapEntities, MapEntitiesError};
 use bevy::prelude::*;
 use bevy::reflect::impl_reflect_value;
 use derive_more::{Deref, DerefMut};
 use rapier2d::prelude::*;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+
+use crate::game::core::maths::structs::Transform2D;
+use crate::game::core::physics::hooks::PhysicsSurface2D;
 
 // Physics state resources
 
@@ -75,21 +80,66 @@ impl Default for MultibodyJointSetRes {
         Self(MultibodyJointSet::new())
     }
 }
-impl_reflect_value!(GravityRes(Serialize, Deserialize));
+
+impl IntegrationParametersRes {
+    /// Steps the physics by one simulated frame.
+    pub fn new(fps: usize) -> Self {
+        Self(IntegrationParameters {
+            dt: 1.0 / fps as f32,
+            ..default()
+        })
+    }
+}
+
+/// Scratch buffers of the physics steps, not part of the rollback state since a step never reads what the previous one
+/// left in them.
+#[derive(Deref, DerefMut, Resource)]
+pub struct PhysicsPipelineRes(pub PhysicsPipeline);
+
+impl Default for PhysicsPipelineRes {
+    fn default() -> Self {
+        Self(PhysicsPipeline::new())
+    }
+}
+
+// Physics state checksums, rapier types are not hashable so their serialized bytes are hashed instead
+
+macro_rules! impl_hash_serialized {
+    ($type:ty) => {
+        impl Hash for $type {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                bincode::serialize(&self.0)
+                    .expect(concat!("failed to serialize ", stringify!($type)))
+                    .hash(state);
+            }
+        }
+    };
+}
+
+impl_hash_serialized!(GravityRes);
+impl_hash_serialized!(BroadPhaseRes);
+impl_hash_serialized!(ColliderSetRes);
+impl_hash_serialized!(NarrowPhaseRes);
+impl_hash_serialized!(RigidBodySetRes);
+impl_hash_serialized!(IslandManagerRes);
+impl_hash_serialized!(ImpulseJointSetRes);
+impl_hash_serialized!(MultibodyJointSetRes);
+
+impl_reflect_value!(GravityRes(Hash, Serialize, Deserialize));
 impl_reflect_value!(CCDSolverRes(Serialize, Deserialize));
-impl_reflect_value!(BroadPhaseRes(Serialize, Deserialize));
-impl_reflect_value!(ColliderSetRes(Serialize, Deserialize));
-impl_reflect_value!(NarrowPhaseRes(Serialize, Deserialize));
-impl_reflect_value!(RigidBodySetRes(Serialize, Deserialize));
-impl_reflect_value!(IslandManagerRes(Serialize, Deserialize));
+impl_reflect_value!(BroadPhaseRes(Hash, Serialize, Deserialize));
+impl_reflect_value!(ColliderSetRes(Hash, Serialize, Deserialize));
+impl_reflect_value!(NarrowPhaseRes(Hash, Serialize, Deserialize));
+impl_reflect_value!(RigidBodySetRes(Hash, Serialize, Deserialize));
+impl_reflect_value!(IslandManagerRes(Hash, Serialize, Deserialize));
 impl_reflect_value!(QueryPipelineRes(Serialize, Deserialize));
-impl_reflect_value!(ImpulseJointSetRes(Serialize, Deserialize));
-impl_reflect_value!(MultibodyJointSetRes(Serialize, Deserialize));
+impl_reflect_value!(ImpulseJointSetRes(Hash, Serialize, Deserialize));
+impl_reflect_value!(MultibodyJointSetRes(Hash, Serialize, Deserialize));
 impl_reflect_value!(IntegrationParametersRes(Serialize, Deserialize));
 
 // Physics ECS components
 
-#[derive(Clone, Debug, Deref, DerefMut, Component, Serialize, Deserialize)]
+#[derive(Clone, Debug, Hash, Deref, DerefMut, Component, Serialize, Deserialize)]
 pub struct RigidBodyHandle2D(pub RigidBodyHandle);
 
 impl Default for RigidBodyHandle2D {
@@ -97,11 +147,266 @@ impl Default for RigidBodyHandle2D {
         Self(RigidBodyHandle::invalid())
     }
 }
-impl_reflect_value!(RigidBodyHandle2D(Serialize, Deserialize));
+impl_reflect_value!(RigidBodyHandle2D(Hash, Serialize, Deserialize));
+
+/// Rigid body the Physics stage creates for the entity, at the position of its `Transform2D` read in meters.
+#[derive(Clone, Debug, Component, Serialize, Deserialize)]
+pub struct RigidBodyDesc2D {
+    pub body_type: RigidBodyType,
+}
+
+impl Default for RigidBodyDesc2D {
+    fn default() -> Self {
+        Self::new(RigidBodyType::Dynamic)
+    }
+}
+
+impl RigidBodyDesc2D {
+    pub fn new(body_type: RigidBodyType) -> Self {
+        Self { body_type }
+    }
+
+    pub fn build(&self, transform: &Transform2D) -> RigidBody {
+        RigidBodyBuilder::new(self.body_type)
+            .rotation(transform.rotation.to_f32())
+            .translation(vector![
+                transform.position.x.to_f32(),
+                transform.position.y.to_f32()
+            ])
+            .build()
+    }
+}
+
+impl_reflect_value!(RigidBodyDesc2D(Serialize, Deserialize));
+
+/// Collider shapes, in meters.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ColliderShape2D {
+    Ball { radius: f32 },
+    Cuboid { half_width: f32, half_height: f32 },
+    Capsule { half_height: f32, radius: f32 },
+    Segment { a: (f32, f32), b: (f32, f32) },
+    ConvexPolygon { points: Vec<(f32, f32)> },
+}
+
+impl ColliderShape2D {
+    pub fn shape(&self) -> SharedShape {
+        match self {
+            Self::Ball { radius } => SharedShape::ball(*radius),
+            Self::Cuboid {
+                half_width,
+                half_height,
+            } => SharedShape::cuboid(*half_width, *half_height),
+            Self::Capsule {
+                half_height,
+                radius,
+            } => SharedShape::capsule_y(*half_height, *radius),
+            Self::Segment { a, b } => SharedShape::segment(point![a.0, a.1], point![b.0, b.1]),
+            Self::ConvexPolygon { points } => Self::convex_hull(points)
+                .expect("degenerated convex polygon, validate the shape before spawning it"),
+        }
+    }
+
+    /// Tells why the shape cannot be built, levels are checked on load.
+    pub fn validate(&self) -> Result<(), String> {
+        match self {
+            Self::ConvexPolygon { points } if Self::convex_hull(points).is_none() => Err(format!(
+                "convex polygon {:?} is degenerated, its points must span an area",
+                points
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn convex_hull(points: &[(f32, f32)]) -> Option<SharedShape> {
+        // parry asserts on less points, and keeps the hull of aligned points as a flat polygon
+        if points.len() < 3 {
+            return None;
+        }
+
+        let points: Vec<_> = points.iter().map(|&(x, y)| point![x, y]).collect();
+        SharedShape::convex_hull(&points).filter(|shape| {
+            shape
+                .as_convex_polygon()
+                .is_some_and(|polygon| polygon.points().len() >= 3)
+        })
+    }
+}
+
+/// Collider the Physics stage attaches to the rigid body of the entity.
+#[derive(Clone, Debug, Component, Serialize, Deserialize)]
+pub struct ColliderDesc2D {
+    pub shape: ColliderShape2D,
+    /// Position relative to the rigid body, in meters.
+    pub offset: (f32, f32),
+    pub restitution: f32,
+    pub friction: f32,
+    pub memberships: u32,
+    pub filter: u32,
+    pub sensor: bool,
+    pub active_events: u32,
+    pub surface: PhysicsSurface2D,
+}
+
+impl Default for ColliderDesc2D {
+    fn default() -> Self {
+        Self::new(ColliderShape2D::Ball { radius: 0.5 })
+    }
+}
+
+impl ColliderDesc2D {
+    pub fn new(shape: ColliderShape2D) -> Self {
+        Self {
+            shape,
+            offset: (0.0, 0.0),
+            restitution: 0.0,
+            friction: ColliderBuilder::default_friction(),
+            memberships: Group::ALL.bits(),
+            filter: Group::ALL.bits(),
+            sensor: false,
+            active_events: ActiveEvents::empty().bits(),
+            surface: PhysicsSurface2D::default(),
+        }
+    }
+
+    pub fn restitution(mut self, restitution: f32) -> Self {
+        self.restitution = restitution;
+        self
+    }
+
+    pub fn collision_groups(mut self, memberships: Group, filter: Group) -> Self {
+        self.memberships = memberships.bits();
+        self.filter = filter.bits();
+        self
+    }
+
+    pub fn active_events(mut self, active_events: ActiveEvents) -> Self {
+        self.active_events = active_events.bits();
+        self
+    }
+
+    pub fn surface(mut self, surface: PhysicsSurface2D) -> Self {
+        self.surface = surface;
+        self
+    }
+
+    pub fn build(&self) -> Collider {
+        self.surface
+            .apply(ColliderBuilder::new(self.shape.shape()))
+            .translation(vector![self.offset.0, self.offset.1])
+            .restitution(self.restitution)
+            .friction(self.friction)
+            .collision_groups(InteractionGroups::new(
+                Group::from_bits_truncate(self.memberships),
+                Group::from_bits_truncate(self.filter),
+            ))
+            .sensor(self.sensor)
+            .active_events(ActiveEvents::from_bits_truncate(self.active_events))
+            .build()
+    }
+}
+
+impl_reflect_value!(ColliderDesc2D(Serialize, Deserialize));
 
 // Physics ECS components book-keeping
 
 #[derive(Clone, Default, Deref, DerefMut, Resource, Serialize, Deserialize)]
 pub struct RigidBodyRemovedEntitiesRes(pub HashMap<Entity, RigidBodyHandle>);
 
-impl_reflect_value!(RigidBodyRemovedEntitiesRes(Serialize, Deserialize));
+impl Hash for RigidBodyRemovedEntitiesRes {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // entity ids differ between peers, only the rigid body handles are the same everywhere
+        let mut rigid_body_handles: Vec<_> = self
+            .0
+            .values()
+            .map(|handle| handle.into_raw_parts())
+            .collect();
+        rigid_body_handles.sort_unstable();
+        rigid_body_handles.hash(state);
+    }
+}
+
+impl MapEntities for RigidBodyRemovedEntitiesRes {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        self.0 = self
+            .0
+            .drain()
+            .map(|(entity, handle)| Ok((entity_map.get(entity)?, handle)))
+            .collect::<Result<_, _>>()?;
+        Ok(())
+    }
+}
+
+impl_reflect_value!(RigidBodyRemovedEntitiesRes(Hash, Serialize, Deserialize));
+
+// Physics events, filled by each step and read by the gameplay systems of the next frame
+
+/// The rigid bodies of an event identify it in checksums, unlike entities they are the same on every peer.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum PhysicsEvent2D {
+    CollisionStarted {
+        entity1: Entity,
+        entity2: Entity,
+        rigid_bodies: (RigidBodyHandle, RigidBodyHandle),
+        sensor: bool,
+    },
+    CollisionStopped {
+        entity1: Entity,
+        entity2: Entity,
+        rigid_bodies: (RigidBodyHandle, RigidBodyHandle),
+        sensor: bool,
+    },
+    ContactForce {
+        entity1: Entity,
+        entity2: Entity,
+        rigid_bodies: (RigidBodyHandle, RigidBodyHandle),
+        total_force_magnitude: f32,
+    },
+}
+
+impl Hash for PhysicsEvent2D {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        std::mem::discriminant(self).hash(state);
+        match *self {
+            Self::CollisionStarted {
+                rigid_bodies,
+                sensor,
+                ..
+            }
+            | Self::CollisionStopped {
+                rigid_bodies,
+                sensor,
+                ..
+            } => (rigid_bodies, sensor).hash(state),
+            Self::ContactForce {
+                rigid_bodies,
+                total_force_magnitude,
+                ..
+            } => (rigid_bodies, total_force_magnitude.to_bits()).hash(state),
+        }
+    }
+}
+
+#[derive(Clone, Default, Hash, Deref, DerefMut, Resource, Serialize, Deserialize)]
+pub struct PhysicsEventsRes(pub Vec<PhysicsEvent2D>);
+
+impl MapEntities for PhysicsEventsRes {
+    fn map_entities(&mut self, entity_map: &EntityMap) -> Result<(), MapEntitiesError> {
+        for event in self.0.iter_mut() {
+            let (PhysicsEvent2D::CollisionStarted {
+                entity1, entity2, ..
+            }
+            | PhysicsEvent2D::CollisionStopped {
+                entity1, entity2, ..
+            }
+            | PhysicsEvent2D::ContactForce {
+                entity1, entity2, ..
+            }) = event;
+            *entity1 = entity_map.get(*entity1)?;
+            *entity2 = entity_map.get(*entity2)?;
+        }
+        Ok(())
+    }
+}
+
+impl_reflect_value!(PhysicsEventsRes(Hash, Serialize, Deserialize));
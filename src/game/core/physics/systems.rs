@@ -1,97 +1,85 @@
 use bevy::prelude::*;
-use bevy_prototype_lyon::prelude::*;
+use bevy_ggrs::Rollback;
 use rapier2d::prelude::*;
 
-use crate::game::core::maths::structs::Transform2D;
+use crate::game::core::maths::structs::{Fixed, Transform2D};
+use crate::game::core::physics::events::PhysicsEventCollector;
+use crate::game::core::physics::hooks::GamePhysicsHooks;
 use crate::game::core::physics::range::scale_physics;
 use crate::game::core::physics::structs::*;
 
 pub const SOLID_PHYSICS_GROUP: rapier2d::geometry::Group = Group::GROUP_1;
 pub const PLAYER_PHYSICS_GROUP: rapier2d::geometry::Group = Group::GROUP_2;
+/// Platforms that only block from above, see the character controller and `PhysicsSurface2D`.
+pub const ONE_WAY_PHYSICS_GROUP: rapier2d::geometry::Group = Group::GROUP_3;
 
+/// Creates the rigid bodies described by the entities spawned since the last step, in rollback id order so every
+/// peer gets the same handles, including when the spawn is simulated again after a rollback. The handle is only
+/// inserted at the end of the stage, so the transform is moved to pixels here rather than by this frame's step.
+#[allow(clippy::type_complexity)]
+pub fn physics_system_create(
+    mut commands: Commands,
+    mut collider_set: ResMut<ColliderSetRes>,
+    mut rigid_body_set: ResMut<RigidBodySetRes>,
+    mut rigid_body_entities: ResMut<RigidBodyRemovedEntitiesRes>,
+    //
+    mut query: Query<
+        (
+            Entity,
+            &Rollback,
+            &mut Transform2D,
+            &RigidBodyDesc2D,
+            Option<&ColliderDesc2D>,
+        ),
+        Without<RigidBodyHandle2D>,
+    >,
+) {
+    let mut descs: Vec<_> = query.iter_mut().collect();
+    descs.sort_unstable_by_key(|(_, rollback, ..)| rollback.id());
+
+    for (entity, _, mut transform, rigid_body_desc, collider_desc) in descs {
+        let rigid_body = rigid_body_desc.build(&transform);
+        transform.position.set(
+            scale_physics(rigid_body.translation().x),
+            scale_physics(rigid_body.translation().y),
+        );
+
+        let rigid_body_handle = rigid_body_set.insert(rigid_body);
+        if let Some(collider_desc) = collider_desc {
+            collider_set.insert_with_parent(
+                collider_desc.build(),
+                rigid_body_handle,
+                &mut rigid_body_set,
+            );
+        }
+
+        info!(
+            "physics_system_create: entity: {:?}, rigid_body_handle: {:?}",
+            entity, rigid_body_handle
+        );
+
+        rigid_body_entities.insert(entity, rigid_body_handle);
+        commands
+            .entity(entity)
+            .insert(RigidBodyHandle2D(rigid_body_handle));
+    }
+}
+
+/// Keeps track of the entity of every rigid body, including the ones not created from a `RigidBodyDesc2D`.
 pub fn physics_system_add(
-    collider_set: Res<ColliderSetRes>,
     rigid_body_set: Res<RigidBodySetRes>,
-    mut commands: Commands,
     mut rigid_body_entities: ResMut<RigidBodyRemovedEntitiesRes>,
     //
-    mut query: Query<(Entity, &RigidBodyHandle2D), Added<RigidBodyHandle2D>>,
+    query: Query<(Entity, &RigidBodyHandle2D), Added<RigidBodyHandle2D>>,
 ) {
-    for (entity, rigid_body_handle) in query.iter_mut() {
+    for (entity, rigid_body_handle) in query.iter() {
         if rigid_body_set.contains(rigid_body_handle.0) {
-            let rigid_body = &rigid_body_set[rigid_body_handle.0];
-
-            for collider_handle in rigid_body.colliders() {
-                let rigid_body_collider = &collider_set[*collider_handle];
-                let rigid_body_collider_shape = rigid_body_collider.shape();
-
-                match rigid_body_collider_shape.shape_type() {
-                    ShapeType::Ball => {
-                        let ball = rigid_body_collider_shape.as_ball().unwrap();
-                        let circle_shape = shapes::Circle {
-                            radius: scale_physics(ball.radius),
-                            ..default()
-                        };
-
-                        commands
-                            .entity(entity)
-                            .insert(SpatialBundle::default())
-                            .with_children(|child_builder| {
-                                child_builder.spawn(GeometryBuilder::build_as(
-                                    &circle_shape,
-                                    DrawMode::Outlined {
-                                        fill_mode: FillMode {
-                                            color: Color::YELLOW,
-                                            options: FillOptions::default(),
-                                        },
-                                        outline_mode: StrokeMode {
-                                            color: Color::BLACK,
-                                            options: StrokeOptions::default(),
-                                        },
-                                    },
-                                    Transform::default(), // TODO: collider transform
-                                ));
-                            });
-                    }
-                    ShapeType::Cuboid => {
-                        let cuboid = rigid_body_collider_shape.as_cuboid().unwrap();
-                        let rectangle_shape = shapes::Rectangle {
-                            extents: Vec2::new(
-                                scale_physics(cuboid.half_extents[0] * 2.0),
-                                scale_physics(cuboid.half_extents[1] * 2.0),
-                            ),
-                            ..default()
-                        };
-
-                        commands
-                            .entity(entity)
-                            .insert(SpatialBundle::default())
-                            .with_children(|child_builder| {
-                                child_builder.spawn(GeometryBuilder::build_as(
-                                    &rectangle_shape,
-                                    DrawMode::Outlined {
-                                        fill_mode: FillMode {
-                                            color: Color::YELLOW,
-                                            options: FillOptions::default(),
-                                        },
-                                        outline_mode: StrokeMode {
-                                            color: Color::BLACK,
-                                            options: StrokeOptions::default(),
-                                        },
-                                    },
-                                    Transform::default(), // TODO: collider transform
-                                ));
-                            });
-                    }
-                    _ => (),
-                };
-            }
-
             rigid_body_entities.insert(entity, rigid_body_handle.0);
         }
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn physics_system_step(
     gravity: Res<GravityRes>,
     integration_parameters: Res<IntegrationParametersRes>,
@@ -105,12 +93,14 @@ pub fn physics_system_step(
     mut query_pipeline: ResMut<QueryPipelineRes>,
     mut impulse_joint_set: ResMut<ImpulseJointSetRes>,
     mut multibody_joint_set: ResMut<MultibodyJointSetRes>,
+    mut physics_events: ResMut<PhysicsEventsRes>,
+    mut physics_pipeline: ResMut<PhysicsPipelineRes>,
+    rigid_body_entities: Res<RigidBodyRemovedEntitiesRes>,
     //
     mut query: Query<(&mut Transform2D, &RigidBodyHandle2D)>,
 ) {
-    let hooks = ();
-    let events = ();
-    let mut physics_pipeline = PhysicsPipeline::new();
+    let hooks = GamePhysicsHooks;
+    let events = PhysicsEventCollector::default();
 
     physics_pipeline.step(
         &gravity,
@@ -130,12 +120,15 @@ pub fn physics_system_step(
 
     query_pipeline.update(&rigid_body_set, &collider_set);
 
+    // the gameplay systems of this frame already read the events of the previous step
+    physics_events.0 = events.into_events(&collider_set, &rigid_body_entities);
+
     for (mut transform, rigid_body_handle) in query.iter_mut() {
         let rigid_body = &rigid_body_set[rigid_body_handle.0];
         let rigid_body_rotation = rigid_body.rotation();
         let rigid_body_translation = rigid_body.translation();
 
-        transform.rotation = rigid_body_rotation.angle();
+        transform.rotation = Fixed::from_f32(rigid_body_rotation.angle());
         transform.position.set(
             scale_physics(rigid_body_translation.x),
             scale_physics(rigid_body_translation.y),
@@ -143,6 +136,9 @@ pub fn physics_system_step(
     }
 }
 
+/// Runs before `physics_system_create`, the handles it creates are only inserted at the end of the stage and a handle
+/// removed by loading a snapshot saved before its body existed must not take the new body with it.
+#[allow(clippy::too_many_arguments)]
 pub fn physics_system_remove(
     removed_entities: RemovedComponents<RigidBodyHandle2D>,
     //
@@ -152,8 +148,15 @@ pub fn physics_system_remove(
     mut impulse_joint_set: ResMut<ImpulseJointSetRes>,
     mut multibody_joint_set: ResMut<MultibodyJointSetRes>,
     mut rigid_body_entities: ResMut<RigidBodyRemovedEntitiesRes>,
+    //
+    query: Query<&RigidBodyHandle2D>,
 ) {
     for removed_entity in removed_entities.iter() {
+        // loading a rollback snapshot removes the handles and inserts them again, their bodies are still in use
+        if query.contains(removed_entity) {
+            continue;
+        }
+
         if let Some(&rigid_body_handle) = rigid_body_entities.get(&removed_entity) {
             rigid_body_set.remove(
                 rigid_body_handle,
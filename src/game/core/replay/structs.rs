@@ -0,0 +1,308 @@
+use bevy::prelude::*;
+use ggrs::InputStatus;
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufReader, Read};
+use std::mem::size_of;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use crate::game::core::input::structs::{BoxInput, FrameInputsRes};
+
+pub const REPLAY_MAGIC: &[u8; 4] = b"FRRP";
+pub const REPLAY_VERSION: u16 = 1;
+pub const REPLAY_SPEEDS: [u32; 4] = [1, 2, 4, 8];
+
+/// Confirmed inputs of every player for every frame of a session.
+///
+/// File layout, little endian: magic, version (u16), input size (u16), number of players (u16),
+/// level checksum (u64), frames per second (u16), number of frames (u32), then the inputs frame by frame and player
+/// by player. The level and the frame rate are the only settings the simulation depends on.
+pub struct Replay {
+    pub num_players: usize,
+    pub level_checksum: u64,
+    pub fps: usize,
+    pub inputs: Vec<Vec<BoxInput>>,
+}
+
+impl Replay {
+    pub fn new(num_players: usize, level_checksum: u64, fps: usize) -> Self {
+        Self {
+            num_players,
+            level_checksum,
+            fps,
+            inputs: Vec::new(),
+        }
+    }
+
+    #[allow(dead_code)]
+    pub fn read(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if &magic != REPLAY_MAGIC {
+            return Err(format!("{} is not a replay file", path.display()).into());
+        }
+        let version = read_u16(&mut reader)?;
+        if version != REPLAY_VERSION {
+            return Err(format!(
+                "{} has replay version {}, expected {}",
+                path.display(),
+                version,
+                REPLAY_VERSION
+            )
+            .into());
+        }
+        let input_size = read_u16(&mut reader)? as usize;
+        if input_size != size_of::<BoxInput>() {
+            return Err(format!(
+                "{} has inputs of {} bytes, expected {}",
+                path.display(),
+                input_size,
+                size_of::<BoxInput>()
+            )
+            .into());
+        }
+
+        let num_players = read_u16(&mut reader)? as usize;
+        let level_checksum = read_u64(&mut reader)?;
+        let fps = read_u16(&mut reader)? as usize;
+        let mut replay = Self::new(num_players, level_checksum, fps);
+        let num_frames = read_u32(&mut reader)? as usize;
+        let mut frame_bytes = vec![0; input_size * replay.num_players];
+        for _ in 0..num_frames {
+            reader.read_exact(&mut frame_bytes)?;
+            replay.inputs.push(
+                frame_bytes
+                    .chunks_exact(input_size)
+                    .map(bytemuck::pod_read_unaligned)
+                    .collect(),
+            );
+        }
+
+        Ok(replay)
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Box<dyn Error>> {
+        let mut bytes = Vec::new();
+
+        bytes.extend_from_slice(REPLAY_MAGIC);
+        bytes.extend_from_slice(&REPLAY_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&(size_of::<BoxInput>() as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.num_players as u16).to_le_bytes());
+        bytes.extend_from_slice(&self.level_checksum.to_le_bytes());
+        bytes.extend_from_slice(&(self.fps as u16).to_le_bytes());
+        bytes.extend_from_slice(&(self.inputs.len() as u32).to_le_bytes());
+        for frame_inputs in &self.inputs {
+            bytes.extend_from_slice(bytemuck::cast_slice(frame_inputs));
+        }
+
+        fs::write(path, bytes)?;
+
+        Ok(())
+    }
+}
+
+/// Non rollback resource recording the inputs of the running session.
+#[derive(Resource)]
+pub struct ReplayRecorderRes {
+    pub path: PathBuf,
+    pub replay: Replay,
+    pub confirmed: Vec<bool>,
+}
+
+impl ReplayRecorderRes {
+    #[allow(dead_code)]
+    pub fn new(path: PathBuf, num_players: usize, level_checksum: u64, fps: usize) -> Self {
+        Self {
+            path,
+            replay: Replay::new(num_players, level_checksum, fps),
+            confirmed: Vec::new(),
+        }
+    }
+
+    /// Records the inputs of a simulated frame, resimulated frames overwrite their predicted inputs.
+    pub fn record(&mut self, frame: u32, inputs: &[(BoxInput, InputStatus)]) {
+        let index = frame as usize - 1;
+        if self.replay.inputs.len() <= index {
+            self.replay.inputs.resize(index + 1, Vec::new());
+            self.confirmed.resize(index + 1, false);
+        }
+
+        self.replay.inputs[index] = inputs.iter().map(|(input, _)| *input).collect();
+        self.confirmed[index] = inputs
+            .iter()
+            .all(|(_, status)| !matches!(status, InputStatus::Predicted));
+    }
+
+    /// Writes every frame up to the first one still relying on predicted inputs.
+    pub fn save(&self) -> Result<(), Box<dyn Error>> {
+        let num_frames = self
+            .confirmed
+            .iter()
+            .position(|confirmed| !confirmed)
+            .unwrap_or(self.confirmed.len());
+
+        Replay {
+            num_players: self.replay.num_players,
+            level_checksum: self.replay.level_checksum,
+            fps: self.replay.fps,
+            inputs: self.replay.inputs[..num_frames].to_vec(),
+        }
+        .write(&self.path)
+    }
+}
+
+/// Non rollback resource holding the replay being played back and its controls.
+#[derive(Resource)]
+pub struct ReplayPlaybackRes {
+    pub replay: Replay,
+    pub frame: usize,
+    pub paused: bool,
+    pub step: bool,
+    pub speed: u32,
+    pub accumulator: f64,
+    pub fps: usize,
+}
+
+impl ReplayPlaybackRes {
+    pub fn new(replay: Replay, fps: usize) -> Self {
+        Self {
+            replay,
+            frame: 0,
+            paused: false,
+            step: false,
+            speed: REPLAY_SPEEDS[0],
+            accumulator: 0.0,
+            fps,
+        }
+    }
+
+    pub fn frames_to_advance(&mut self, delta: Duration) -> usize {
+        if self.paused {
+            self.accumulator = 0.0;
+            return std::mem::take(&mut self.step) as usize;
+        }
+
+        self.accumulator += delta.as_secs_f64() * (self.speed as usize * self.fps) as f64;
+        let frames = self.accumulator as usize;
+        self.accumulator -= frames as f64;
+
+        frames
+    }
+
+    pub fn next_inputs(&mut self) -> Option<Vec<(BoxInput, InputStatus)>> {
+        let inputs = self.replay.inputs.get(self.frame)?;
+        self.frame += 1;
+
+        Some(
+            inputs
+                .iter()
+                .map(|input| (*input, InputStatus::Confirmed))
+                .collect(),
+        )
+    }
+}
+
+/// Runs the rollback schedule with the replay inputs, replaces the rollback stage during playback.
+pub struct ReplayStage {
+    pub schedule: Schedule,
+}
+
+impl Stage for ReplayStage {
+    fn run(&mut self, world: &mut World) {
+        let delta = world.resource::<Time>().delta();
+        let frames = world
+            .resource_mut::<ReplayPlaybackRes>()
+            .frames_to_advance(delta);
+
+        for _ in 0..frames {
+            match world.resource_mut::<ReplayPlaybackRes>().next_inputs() {
+                Some(inputs) => world.resource_mut::<FrameInputsRes>().0 = inputs,
+                None => break,
+            }
+
+            self.schedule.run_once(world);
+        }
+    }
+}
+
+fn read_u16(reader: &mut impl Read) -> Result<u16, Box<dyn Error>> {
+    let mut bytes = [0; 2];
+    reader.read_exact(&mut bytes)?;
+    Ok(u16::from_le_bytes(bytes))
+}
+
+fn read_u32(reader: &mut impl Read) -> Result<u32, Box<dyn Error>> {
+    let mut bytes = [0; 4];
+    reader.read_exact(&mut bytes)?;
+    Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_u64(reader: &mut impl Read) -> Result<u64, Box<dyn Error>> {
+    let mut bytes = [0; 8];
+    reader.read_exact(&mut bytes)?;
+    Ok(u64::from_le_bytes(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::core::input::structs::{INPUT_JUMP, INPUT_LEFT, INPUT_RIGHT};
+    use std::env;
+
+    fn temp_path(name: &str) -> PathBuf {
+        env::temp_dir().join(format!("frogrs-{}-{}.replay", name, std::process::id()))
+    }
+
+    fn sample_replay() -> Replay {
+        let mut replay = Replay::new(2, 0x0123_4567_89ab_cdef, 30);
+        replay.inputs = vec![
+            vec![
+                BoxInput::from_buttons(INPUT_LEFT),
+                BoxInput::from_buttons(INPUT_JUMP),
+            ],
+            vec![
+                BoxInput {
+                    inp: INPUT_RIGHT,
+                    axis_x: 64,
+                    axis_y: -3,
+                },
+                BoxInput::from_buttons(0),
+            ],
+        ];
+        replay
+    }
+
+    #[test]
+    fn replay_round_trips_through_a_file() {
+        let path = temp_path("round-trip");
+        let replay = sample_replay();
+
+        replay.write(&path).unwrap();
+        let loaded = Replay::read(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+
+        assert_eq!(loaded.num_players, replay.num_players);
+        assert_eq!(loaded.level_checksum, replay.level_checksum);
+        assert_eq!(loaded.fps, replay.fps);
+        assert!(loaded.inputs == replay.inputs);
+    }
+
+    #[test]
+    fn replay_of_another_version_is_rejected() {
+        let path = temp_path("version");
+        sample_replay().write(&path).unwrap();
+        let mut bytes = fs::read(&path).unwrap();
+        bytes[4..6].copy_from_slice(&(REPLAY_VERSION - 1).to_le_bytes());
+        fs::write(&path, bytes).unwrap();
+
+        let result = Replay::read(&path);
+        fs::remove_file(&path).unwrap();
+
+        let err = result.err().unwrap().to_string();
+        assert!(err.contains("replay version"), "{}", err);
+    }
+}
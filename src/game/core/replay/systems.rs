@@ -0,0 +1,64 @@
+use bevy::app::AppExit;
+use bevy::prelude::*;
+use log::{error, info};
+
+use crate::game::core::frame::structs::FrameCount;
+use crate::game::core::input::structs::FrameInputsRes;
+use crate::game::core::replay::structs::{ReplayPlaybackRes, ReplayRecorderRes, REPLAY_SPEEDS};
+
+pub fn replay_record_system(
+    frame_count: Res<FrameCount>,
+    frame_inputs: Res<FrameInputsRes>,
+    replay_recorder: Option<ResMut<ReplayRecorderRes>>,
+) {
+    if let Some(mut replay_recorder) = replay_recorder {
+        replay_recorder.record(frame_count.frame, &frame_inputs);
+    }
+}
+
+pub fn replay_save_system(
+    replay_recorder: Option<Res<ReplayRecorderRes>>,
+    mut app_exit_events: EventReader<AppExit>,
+) {
+    if let Some(replay_recorder) = replay_recorder {
+        if app_exit_events.iter().next().is_some() {
+            match replay_recorder.save() {
+                Ok(()) => info!("replay saved to {}", replay_recorder.path.display()),
+                Err(e) => error!(
+                    "failed to save replay to {}: {}",
+                    replay_recorder.path.display(),
+                    e
+                ),
+            }
+        }
+    }
+}
+
+pub fn replay_controls_system(
+    keyboard: Res<Input<KeyCode>>,
+    mut replay_playback: ResMut<ReplayPlaybackRes>,
+) {
+    if keyboard.just_pressed(KeyCode::Space) {
+        replay_playback.paused = !replay_playback.paused;
+        info!(
+            "replay {} at frame {}",
+            if replay_playback.paused {
+                "paused"
+            } else {
+                "resumed"
+            },
+            replay_playback.frame
+        );
+    }
+    if keyboard.just_pressed(KeyCode::Right) && replay_playback.paused {
+        replay_playback.step = true;
+    }
+    if keyboard.just_pressed(KeyCode::F) {
+        let index = REPLAY_SPEEDS
+            .iter()
+            .position(|&speed| speed == replay_playback.speed)
+            .unwrap_or(0);
+        replay_playback.speed = REPLAY_SPEEDS[(index + 1) % REPLAY_SPEEDS.len()];
+        info!("replay speed x{}", replay_playback.speed);
+    }
+}
@@ -0,0 +1,265 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt::Display;
+use std::fs;
+use std::ops::RangeInclusive;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use structopt::StructOpt;
+
+use crate::game::core::camera::structs::CameraMode;
+use crate::game::core::level::structs::Level;
+use crate::game::GAME_FPS;
+
+/// Session and window parameters, every peer of a session must use the same simulation values.
+#[derive(Clone, Debug, Serialize, Deserialize, Resource)]
+#[serde(default, deny_unknown_fields)]
+pub struct GameSettings {
+    pub input_delay: usize,
+    pub max_prediction_window: usize,
+    /// Frames between two checksum exchanges, 0 disables desync detection.
+    pub desync_detection_interval: u32,
+    pub fps: usize,
+    pub window_width: f32,
+    pub window_height: f32,
+    pub disconnect_timeout_ms: u64,
+    pub disconnect_notify_delay_ms: u64,
+    /// Gamepad stick deflection below which the stick is considered centered.
+    pub gamepad_deadzone: f32,
+    /// Level file, the built-in level when missing.
+    pub level: Option<PathBuf>,
+    pub camera_mode: CameraMode,
+}
+
+impl Default for GameSettings {
+    fn default() -> Self {
+        Self {
+            input_delay: 2,
+            max_prediction_window: 12,
+            desync_detection_interval: 10,
+            fps: GAME_FPS,
+            window_width: 720.,
+            window_height: 720.,
+            disconnect_timeout_ms: 2000,
+            disconnect_notify_delay_ms: 500,
+            gamepad_deadzone: 0.3,
+            level: None,
+            camera_mode: CameraMode::Follow,
+        }
+    }
+}
+
+impl GameSettings {
+    /// Reads the settings from a `.ron` or `.toml` file, missing fields keep their default value.
+    pub fn from_file(path: &Path) -> Result<Self, Box<dyn Error>> {
+        let contents = fs::read_to_string(path)
+            .map_err(|err| format!("cannot read settings {}: {}", path.display(), err))?;
+
+        let settings = match path.extension().and_then(|extension| extension.to_str()) {
+            Some("ron") => ron::from_str(&contents)
+                .map_err(|err| format!("invalid settings {}: {}", path.display(), err))?,
+            Some("toml") => toml::from_str(&contents)
+                .map_err(|err| format!("invalid settings {}: {}", path.display(), err))?,
+            _ => {
+                return Err(format!(
+                    "unsupported settings format {}, expected .ron or .toml",
+                    path.display()
+                )
+                .into())
+            }
+        };
+
+        Ok(settings)
+    }
+
+    pub fn validate(&self) -> Result<(), Box<dyn Error>> {
+        check_range("input_delay", self.input_delay, 0..=10)?;
+        check_range("max_prediction_window", self.max_prediction_window, 1..=30)?;
+        check_range(
+            "desync_detection_interval",
+            self.desync_detection_interval,
+            0..=600,
+        )?;
+        check_range("fps", self.fps, 10..=240)?;
+        check_range("window_width", self.window_width, 240.0..=7680.0)?;
+        check_range("window_height", self.window_height, 240.0..=4320.0)?;
+        check_range(
+            "disconnect_timeout_ms",
+            self.disconnect_timeout_ms,
+            100..=60000,
+        )?;
+        check_range(
+            "disconnect_notify_delay_ms",
+            self.disconnect_notify_delay_ms,
+            0..=self.disconnect_timeout_ms,
+        )?;
+        check_range("gamepad_deadzone", self.gamepad_deadzone, 0.0..=0.9)?;
+
+        Ok(())
+    }
+
+    pub fn load_level(&self) -> Result<Level, Box<dyn Error>> {
+        Level::load(self.level.as_deref())
+    }
+
+    #[allow(dead_code)]
+    pub fn disconnect_timeout(&self) -> Duration {
+        Duration::from_millis(self.disconnect_timeout_ms)
+    }
+
+    #[allow(dead_code)]
+    pub fn disconnect_notify_delay(&self) -> Duration {
+        Duration::from_millis(self.disconnect_notify_delay_ms)
+    }
+}
+
+/// Command line options shared by the binaries, they take precedence over the settings file.
+#[derive(StructOpt)]
+pub struct GameSettingsArgs {
+    #[structopt(long, parse(from_os_str))]
+    settings: Option<PathBuf>,
+    #[structopt(long)]
+    input_delay: Option<usize>,
+    #[structopt(long)]
+    max_prediction_window: Option<usize>,
+    #[structopt(long)]
+    desync_detection_interval: Option<u32>,
+    #[structopt(long)]
+    fps: Option<usize>,
+    #[structopt(long)]
+    window_width: Option<f32>,
+    #[structopt(long)]
+    window_height: Option<f32>,
+    #[structopt(long)]
+    disconnect_timeout_ms: Option<u64>,
+    #[structopt(long)]
+    disconnect_notify_delay_ms: Option<u64>,
+    #[structopt(long)]
+    gamepad_deadzone: Option<f32>,
+    /// Level file, every peer of a session must load the same one
+    #[structopt(long, parse(from_os_str))]
+    level: Option<PathBuf>,
+    #[structopt(long)]
+    camera_mode: Option<CameraMode>,
+}
+
+impl GameSettingsArgs {
+    pub fn load(&self) -> Result<GameSettings, Box<dyn Error>> {
+        let mut settings = match &self.settings {
+            Some(path) => GameSettings::from_file(path)?,
+            None => GameSettings::default(),
+        };
+
+        if let Some(input_delay) = self.input_delay {
+            settings.input_delay = input_delay;
+        }
+        if let Some(max_prediction_window) = self.max_prediction_window {
+            settings.max_prediction_window = max_prediction_window;
+        }
+        if let Some(desync_detection_interval) = self.desync_detection_interval {
+            settings.desync_detection_interval = desync_detection_interval;
+        }
+        if let Some(fps) = self.fps {
+            settings.fps = fps;
+        }
+        if let Some(window_width) = self.window_width {
+            settings.window_width = window_width;
+        }
+        if let Some(window_height) = self.window_height {
+            settings.window_height = window_height;
+        }
+        if let Some(disconnect_timeout_ms) = self.disconnect_timeout_ms {
+            settings.disconnect_timeout_ms = disconnect_timeout_ms;
+        }
+        if let Some(disconnect_notify_delay_ms) = self.disconnect_notify_delay_ms {
+            settings.disconnect_notify_delay_ms = disconnect_notify_delay_ms;
+        }
+        if let Some(gamepad_deadzone) = self.gamepad_deadzone {
+            settings.gamepad_deadzone = gamepad_deadzone;
+        }
+        if let Some(level) = &self.level {
+            settings.level = Some(level.clone());
+        }
+        if let Some(camera_mode) = self.camera_mode {
+            settings.camera_mode = camera_mode;
+        }
+
+        settings.validate()?;
+
+        Ok(settings)
+    }
+}
+
+fn check_range<T: PartialOrd + Display>(
+    name: &str,
+    value: T,
+    range: RangeInclusive<T>,
+) -> Result<(), Box<dyn Error>> {
+    if range.contains(&value) {
+        Ok(())
+    } else {
+        Err(format!(
+            "invalid setting {} = {}, expected a value between {} and {}",
+            name,
+            value,
+            range.start(),
+            range.end()
+        )
+        .into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validate_accepts_the_defaults() {
+        assert!(GameSettings::default().validate().is_ok());
+    }
+
+    #[test]
+    fn validate_rejects_values_out_of_range() {
+        let invalid_settings = [
+            GameSettings {
+                max_prediction_window: 0,
+                ..default()
+            },
+            GameSettings {
+                fps: 500,
+                ..default()
+            },
+            GameSettings {
+                window_width: 100.0,
+                ..default()
+            },
+            GameSettings {
+                gamepad_deadzone: 1.0,
+                ..default()
+            },
+            // the notify delay cannot be longer than the timeout
+            GameSettings {
+                disconnect_timeout_ms: 1000,
+                disconnect_notify_delay_ms: 1500,
+                ..default()
+            },
+        ];
+
+        for settings in invalid_settings {
+            assert!(settings.validate().is_err(), "{:?}", settings);
+        }
+    }
+
+    #[test]
+    fn validate_names_the_invalid_setting() {
+        let settings = GameSettings {
+            input_delay: 11,
+            ..default()
+        };
+
+        let err = settings.validate().unwrap_err().to_string();
+
+        assert!(err.contains("input_delay = 11"), "{}", err);
+    }
+}
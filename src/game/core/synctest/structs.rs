@@ -0,0 +1,43 @@
+use bevy::prelude::*;
+use std::collections::HashMap;
+
+/// Number of frames a checksum is kept around, must be greater than the synctest check distance.
+pub const SYNCTEST_CHECKSUM_HISTORY: u32 = 128;
+
+#[allow(dead_code)]
+#[derive(Clone, Debug)]
+pub struct SyncTestMismatch {
+    pub frame: u32,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+/// Non rollback resource remembering the checksum of every simulated frame,
+/// a resimulated frame yielding a different checksum is a desync.
+#[derive(Default, Resource)]
+pub struct SyncTestChecksumsRes {
+    pub checksums: HashMap<u32, u64>,
+    pub mismatch: Option<SyncTestMismatch>,
+}
+
+impl SyncTestChecksumsRes {
+    pub fn record(&mut self, frame: u32, checksum: u64) {
+        match self.checksums.get(&frame) {
+            Some(&expected) if expected != checksum => {
+                if self.mismatch.is_none() {
+                    self.mismatch = Some(SyncTestMismatch {
+                        frame,
+                        expected,
+                        actual: checksum,
+                    });
+                }
+            }
+            Some(_) => (),
+            None => {
+                self.checksums.insert(frame, checksum);
+                self.checksums
+                    .remove(&frame.wrapping_sub(SYNCTEST_CHECKSUM_HISTORY));
+            }
+        }
+    }
+}
@@ -0,0 +1,18 @@
+use bevy::prelude::*;
+
+use crate::game::core::frame::structs::FrameCount;
+use crate::game::core::synctest::structs::SyncTestChecksumsRes;
+use crate::game::core::synctest::utilities::world_checksum;
+
+pub fn synctest_checksum_system(world: &mut World) {
+    if !world.contains_resource::<SyncTestChecksumsRes>() {
+        return;
+    }
+
+    let frame = world.resource::<FrameCount>().frame;
+    let checksum = world_checksum(world);
+
+    world
+        .resource_mut::<SyncTestChecksumsRes>()
+        .record(frame, checksum);
+}
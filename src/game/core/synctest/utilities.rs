@@ -0,0 +1,66 @@
+use bevy::prelude::*;
+use bevy_ggrs::Rollback;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+
+use crate::game::core::anim::structs::{AnimationEventsRes, SpriteSheetAnimation};
+use crate::game::core::controller::structs::CharacterController2D;
+use crate::game::core::frame::structs::FrameCount;
+use crate::game::core::input::structs::PreviousInput2D;
+use crate::game::core::maths::structs::Transform2D;
+use crate::game::core::physics::structs::*;
+use crate::game::player::structs::Player2D;
+
+pub fn world_checksum(world: &mut World) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    world.resource::<FrameCount>().hash(&mut hasher);
+    world.resource::<GravityRes>().hash(&mut hasher);
+    world.resource::<BroadPhaseRes>().hash(&mut hasher);
+    world.resource::<ColliderSetRes>().hash(&mut hasher);
+    world.resource::<NarrowPhaseRes>().hash(&mut hasher);
+    world.resource::<RigidBodySetRes>().hash(&mut hasher);
+    world.resource::<IslandManagerRes>().hash(&mut hasher);
+    world.resource::<ImpulseJointSetRes>().hash(&mut hasher);
+    world.resource::<MultibodyJointSetRes>().hash(&mut hasher);
+    world
+        .resource::<RigidBodyRemovedEntitiesRes>()
+        .hash(&mut hasher);
+    world.resource::<PhysicsEventsRes>().hash(&mut hasher);
+    world.resource::<AnimationEventsRes>().hash(&mut hasher);
+    let mut checksum = hasher.finish();
+
+    let mut query = world.query::<(
+        &Rollback,
+        Option<&Player2D>,
+        Option<&PreviousInput2D>,
+        Option<&CharacterController2D>,
+        Option<&Transform2D>,
+        Option<&RigidBodyHandle2D>,
+        Option<&SpriteSheetAnimation>,
+    )>();
+    for (
+        rollback,
+        player,
+        previous_input,
+        character_controller,
+        transform,
+        rigid_body_handle,
+        sprite_sheet_animation,
+    ) in query.iter(world)
+    {
+        let mut hasher = DefaultHasher::new();
+
+        rollback.id().hash(&mut hasher);
+        player.map(|player| player.handle).hash(&mut hasher);
+        previous_input.hash(&mut hasher);
+        character_controller.hash(&mut hasher);
+        transform.hash(&mut hasher);
+        rigid_body_handle.hash(&mut hasher);
+        sprite_sheet_animation.hash(&mut hasher);
+
+        // entities are iterated in no particular order, combine their hashes commutatively
+        checksum = checksum.wrapping_add(hasher.finish());
+    }
+
+    checksum
+}
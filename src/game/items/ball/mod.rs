@@ -1,14 +1,18 @@
 use bevy::prelude::*;
+use bevy_ggrs::RollbackIdProvider;
 use rapier2d::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::game::core::physics::structs::{ColliderSetRes, RigidBodyHandle2D, RigidBodySetRes};
-use crate::game::core::{frame::structs::FrameCount, physics::systems::PLAYER_PHYSICS_GROUP};
+use crate::game::core::frame::structs::FrameCount;
+use crate::game::core::level::structs::LevelRes;
+use crate::game::core::physics::structs::{ColliderDesc2D, ColliderShape2D, RigidBodyDesc2D};
+use crate::game::core::physics::systems::{ONE_WAY_PHYSICS_GROUP, PLAYER_PHYSICS_GROUP};
 use crate::game::core::{
     maths::structs::{Transform2D, Vector2D},
     physics::systems::SOLID_PHYSICS_GROUP,
 };
 
-#[derive(Default, Reflect, Component)]
+#[derive(Default, Reflect, Component, Serialize, Deserialize)]
 pub struct Ball2D {}
 
 pub fn ball_system(
@@ -25,47 +29,26 @@ pub fn ball_system(
 }
 
 pub fn startup_ball_system(
+    level: Res<LevelRes>,
+    //
     mut commands: Commands,
-    mut collider_set: ResMut<ColliderSetRes>,
-    mut rigid_body_set: ResMut<RigidBodySetRes>,
+    mut rollback_id_provider: ResMut<RollbackIdProvider>,
 ) {
-    let transform = Transform2D::from_position(Vector2D::new(5.0, 3.0));
-
-    commands
-        .spawn_empty()
-        .insert(Ball2D::default())
-        .insert(Transform::default())
-        .insert(GlobalTransform::default())
-        //
-        .insert(create_ball_rigid_body(
-            &transform,
-            &mut collider_set,
-            &mut rigid_body_set,
-        ))
-        .insert(transform);
-}
-
-pub fn create_ball_rigid_body(
-    transform: &Transform2D,
-    collider_set: &mut ColliderSetRes,
-    rigid_body_set: &mut RigidBodySetRes,
-) -> RigidBodyHandle2D {
-    let rigid_body = RigidBodyBuilder::dynamic()
-        .rotation(transform.rotation)
-        .translation(vector![transform.position.x, transform.position.y])
-        .build();
-    let rigid_body_handle = rigid_body_set.insert(rigid_body);
-    let rigid_body_collider = ColliderBuilder::ball(1.0)
-        .restitution(0.7)
-        .collision_groups(InteractionGroups::new(
-            PLAYER_PHYSICS_GROUP,
-            SOLID_PHYSICS_GROUP | PLAYER_PHYSICS_GROUP,
-        ))
-        .build();
-
-    collider_set.insert_with_parent(rigid_body_collider, rigid_body_handle, rigid_body_set);
-
-    info!("create_ball_rigid_body: handle: {:?}", rigid_body_handle);
-
-    RigidBodyHandle2D(rigid_body_handle)
+    for &(x, y) in level.ball_spawns.iter() {
+        commands.spawn((
+            Ball2D::default(),
+            Transform::default(),
+            GlobalTransform::default(),
+            Transform2D::from_position(Vector2D::from_f32(x, y)),
+            RigidBodyDesc2D::new(RigidBodyType::Dynamic),
+            ColliderDesc2D::new(ColliderShape2D::Ball { radius: 1.0 })
+                .restitution(0.7)
+                .collision_groups(
+                    PLAYER_PHYSICS_GROUP,
+                    SOLID_PHYSICS_GROUP | PLAYER_PHYSICS_GROUP | ONE_WAY_PHYSICS_GROUP,
+                )
+                .active_events(ActiveEvents::COLLISION_EVENTS | ActiveEvents::CONTACT_FORCE_EVENTS),
+            rollback_id_provider.next(),
+        ));
+    }
 }
@@ -5,27 +5,67 @@ pub mod player;
 use std::net::SocketAddr;
 
 use bevy::prelude::*;
-use bevy_ggrs::GGRSPlugin;
+use bevy_ggrs::{RollbackIdProvider, Session};
 use bevy_prototype_lyon::plugin::ShapePlugin;
-use ggrs::Config;
+use ggrs::{Config, PlayerHandle};
+use log::error;
 
-use crate::game::core::anim::systems::animate_sprite_system;
+use crate::game::core::anim::structs::{AnimationEventsRes, SpriteSheetAnimation};
+use crate::game::core::anim::systems::{animate_sprite_system, sprite_animation_system};
+use crate::game::core::camera::structs::CameraRes;
+use crate::game::core::camera::systems::{
+    camera_spawn_system, camera_system, startup_camera_system,
+};
+use crate::game::core::controller::structs::CharacterController2D;
 use crate::game::core::debug::debug_system;
+use crate::game::core::diagnostics::structs::NetworkDiagnosticsRes;
+use crate::game::core::diagnostics::systems::{
+    network_diagnostics_system, rollback_diagnostics_system, startup_network_diagnostics_system,
+};
+use crate::game::core::forensics::structs::{
+    DesyncDetectedEvent, SnapshotHistoryRes, SNAPSHOT_HISTORY_FRAMES,
+};
+use crate::game::core::forensics::systems::{desync_dump_system, snapshot_system};
 use crate::game::core::frame::structs::FrameCount;
 use crate::game::core::frame::systems::frame_system;
-use crate::game::core::input::structs::BoxInput;
-use crate::game::core::input::systems::input_system;
+use crate::game::core::input::structs::{
+    BoxInput, FrameInputsRes, GamepadAssignmentsRes, InputBindingsRes, PreviousInput2D,
+    RebindingRes,
+};
+use crate::game::core::input::systems::{
+    gamepad_assignment_system, input_system, previous_input_system, rebinding_system,
+    scripted_input_system, startup_rebinding_system,
+};
+use crate::game::core::level::systems::{startup_level_sprites_system, startup_level_system};
 use crate::game::core::maths::structs::Transform2D;
 use crate::game::core::maths::systems::sync_transform_system;
+use crate::game::core::network::structs::{NetworkStatusRes, RollbackStage};
+use crate::game::core::network::systems::{
+    network_events_system, network_status_system, startup_network_status_system,
+};
+use crate::game::core::physics::debug::{
+    physics_debug_system, startup_physics_debug_system, PhysicsDebugRes,
+};
 use crate::game::core::physics::structs::*;
 use crate::game::core::physics::systems::{
-    physics_system_add, physics_system_remove, physics_system_step,
+    physics_system_add, physics_system_create, physics_system_remove, physics_system_step,
+};
+use crate::game::core::replay::structs::{Replay, ReplayPlaybackRes, ReplayStage};
+use crate::game::core::replay::systems::{
+    replay_controls_system, replay_record_system, replay_save_system,
 };
+use crate::game::core::settings::structs::GameSettings;
+use crate::game::core::synctest::systems::synctest_checksum_system;
 use crate::game::items::ball::{ball_system, startup_ball_system, Ball2D};
 use crate::game::player::structs::Player2D;
-use crate::game::player::systems::{player_system, startup_player_system};
+use crate::game::player::systems::{
+    player_animation_system, player_sprite_system, player_system, startup_player_system,
+};
 
 pub const GAME_FPS: usize = 60;
+/// Headless sessions are not bound to the display, simulate them as fast as reasonably possible.
+#[allow(dead_code)]
+pub const HEADLESS_SPEEDUP: usize = 10;
 
 #[derive(Debug)]
 pub struct GameConfig;
@@ -42,97 +82,263 @@ enum RollbackStages {
     Synchronization,
 }
 
+#[derive(Eq, Hash, Clone, Debug, PartialEq, StageLabel)]
+struct RollbackStageLabel;
+
+#[derive(Eq, Hash, Clone, Debug, PartialEq, StageLabel)]
+struct ReplayStageLabel;
+
 pub trait GameApp {
-    fn insert_game(&mut self, window_title: &str) -> &mut Self;
+    #[allow(dead_code)]
+    fn insert_game(&mut self, window_title: &str, settings: &GameSettings) -> &mut Self;
+    #[allow(dead_code)]
+    fn insert_headless_game(&mut self, settings: &GameSettings) -> &mut Self;
+    #[allow(dead_code)]
+    fn insert_replay_game(
+        &mut self,
+        window_title: &str,
+        settings: &GameSettings,
+        replay: Replay,
+    ) -> &mut Self;
 }
 
 impl GameApp for App {
-    fn insert_game(&mut self, window_title: &str) -> &mut Self {
-        GGRSPlugin::<GameConfig>::new()
-            // define frequency of rollback game logic update
-            .with_update_frequency(GAME_FPS)
-            // define system that returns inputs given a player handle, so GGRS can send the inputs around
-            .with_input_system(input_system)
-            // register types of resources you want to be rolled back
-            .register_rollback_resource::<FrameCount>()
-            .register_rollback_resource::<GravityRes>()
-            .register_rollback_resource::<CCDSolverRes>()
-            .register_rollback_resource::<BroadPhaseRes>()
-            .register_rollback_resource::<ColliderSetRes>()
-            .register_rollback_resource::<NarrowPhaseRes>()
-            .register_rollback_resource::<RigidBodySetRes>()
-            .register_rollback_resource::<IslandManagerRes>()
-            .register_rollback_resource::<QueryPipelineRes>()
-            .register_rollback_resource::<ImpulseJointSetRes>()
-            .register_rollback_resource::<MultibodyJointSetRes>()
-            .register_rollback_resource::<IntegrationParametersRes>()
-            .register_rollback_resource::<RigidBodyRemovedEntitiesRes>()
-            // register types of components you want to be rolled back
-            .register_rollback_component::<Ball2D>()
-            .register_rollback_component::<Player2D>()
-            .register_rollback_component::<Transform2D>()
-            .register_rollback_component::<RigidBodyHandle2D>()
-            // these systems will be executed as part of the advance frame update
-            .with_rollback_schedule(
-                Schedule::default()
-                    .with_stage(
-                        RollbackStages::Game,
-                        SystemStage::single_threaded()
-                            .with_system(ball_system)
-                            .with_system(frame_system)
-                            .with_system(player_system)
-                            .with_system(animate_sprite_system),
-                    )
-                    .with_stage_after(
-                        RollbackStages::Game,
-                        RollbackStages::Physics,
-                        SystemStage::single_threaded()
-                            .with_system(physics_system_add)
-                            .with_system(physics_system_step)
-                            .with_system(physics_system_remove),
-                    )
-                    .with_stage_after(
-                        RollbackStages::Physics,
-                        RollbackStages::Synchronization,
-                        SystemStage::parallel().with_system(sync_transform_system),
-                    ),
-            )
-            // make it happen in the bevy app
-            .build(self);
+    fn insert_game(&mut self, window_title: &str, settings: &GameSettings) -> &mut Self {
+        insert_game_window(self, window_title, settings);
+
+        let input_bindings = InputBindingsRes::load().unwrap_or_else(|e| {
+            error!(
+                "failed to load the input bindings, using the defaults: {}",
+                e
+            );
+            InputBindingsRes::default()
+        });
+
+        self.insert_resource(input_bindings)
+            .insert_resource(RebindingRes::default())
+            .insert_resource(GamepadAssignmentsRes::default())
+            .add_startup_system(startup_rebinding_system)
+            .add_system(gamepad_assignment_system)
+            .add_system(rebinding_system);
+        insert_game_simulation(self, settings, settings.fps, input_system)
+    }
+
+    fn insert_headless_game(&mut self, settings: &GameSettings) -> &mut Self {
+        self.add_plugins(MinimalPlugins);
+
+        insert_game_simulation(
+            self,
+            settings,
+            settings.fps * HEADLESS_SPEEDUP,
+            scripted_input_system,
+        )
+    }
+
+    fn insert_replay_game(
+        &mut self,
+        window_title: &str,
+        settings: &GameSettings,
+        replay: Replay,
+    ) -> &mut Self {
+        insert_game_window(self, window_title, settings);
 
         self
             //
-            .add_plugins(DefaultPlugins.set(WindowPlugin {
-                window: WindowDescriptor {
-                    width: 720.,
-                    height: 720.,
-                    title: window_title.to_owned(),
-                    ..default()
-                },
-                ..default()
-            }))
-            .add_plugin(ShapePlugin)
-            //
-            .insert_resource(Msaa { samples: 4 })
-            //
-            .insert_resource(FrameCount::default())
-            .insert_resource(GravityRes::default())
-            .insert_resource(CCDSolverRes::default())
-            .insert_resource(BroadPhaseRes::default())
-            .insert_resource(ColliderSetRes::default())
-            .insert_resource(NarrowPhaseRes::default())
-            .insert_resource(RigidBodySetRes::default())
-            .insert_resource(IslandManagerRes::default())
-            .insert_resource(QueryPipelineRes::default())
-            .insert_resource(ImpulseJointSetRes::default())
-            .insert_resource(MultibodyJointSetRes::default())
-            .insert_resource(IntegrationParametersRes::default())
-            .insert_resource(RigidBodyRemovedEntitiesRes::default())
+            .insert_resource(RollbackIdProvider::default())
+            .insert_resource(ReplayPlaybackRes::new(replay, settings.fps))
             //
-            .add_system(debug_system.at_end())
-            .add_startup_system(startup_ball_system)
-            .add_startup_system(startup_player_system);
+            .add_stage_before(
+                CoreStage::Update,
+                ReplayStageLabel,
+                ReplayStage {
+                    schedule: rollback_schedule(),
+                },
+            )
+            .add_system(replay_controls_system);
 
-        self
+        insert_game_resources(self, settings)
     }
 }
+
+pub fn num_players(session: &Session<GameConfig>) -> usize {
+    match session {
+        Session::P2PSession(s) => s.num_players(),
+        Session::SyncTestSession(s) => s.num_players(),
+        Session::SpectatorSession(s) => s.num_players(),
+    }
+}
+
+fn insert_game_window(app: &mut App, window_title: &str, settings: &GameSettings) {
+    app
+        //
+        .add_plugins(DefaultPlugins.set(WindowPlugin {
+            window: WindowDescriptor {
+                width: settings.window_width,
+                height: settings.window_height,
+                title: window_title.to_owned(),
+                ..default()
+            },
+            ..default()
+        }))
+        .add_plugin(ShapePlugin)
+        //
+        .insert_resource(Msaa { samples: 4 })
+        .insert_resource(NetworkDiagnosticsRes::default())
+        .insert_resource(PhysicsDebugRes::default())
+        .insert_resource(CameraRes::new(settings.camera_mode))
+        //
+        .add_startup_system(startup_network_diagnostics_system)
+        .add_startup_system(startup_network_status_system)
+        .add_startup_system(startup_physics_debug_system)
+        .add_startup_system(startup_level_sprites_system)
+        .add_startup_system(startup_camera_system)
+        .add_system(debug_system.at_end())
+        .add_system(network_diagnostics_system)
+        .add_system(network_status_system.after(network_events_system))
+        .add_system(player_sprite_system)
+        .add_system(sprite_animation_system)
+        .add_system(physics_debug_system)
+        .add_system(camera_spawn_system)
+        .add_system(camera_system.after(camera_spawn_system));
+}
+
+fn insert_game_simulation<'a, Params>(
+    app: &'a mut App,
+    settings: &GameSettings,
+    update_frequency: usize,
+    input_system: impl IntoSystem<PlayerHandle, BoxInput, Params>,
+) -> &'a mut App {
+    let rollback_stage = RollbackStage::new(
+        &mut app.world,
+        rollback_schedule(),
+        update_frequency,
+        input_system,
+    )
+    // register types of resources you want to be rolled back
+    .register_rollback_resource::<FrameCount>()
+    .register_rollback_resource::<GravityRes>()
+    .register_rollback_resource::<CCDSolverRes>()
+    .register_rollback_resource::<BroadPhaseRes>()
+    .register_rollback_resource::<ColliderSetRes>()
+    .register_rollback_resource::<NarrowPhaseRes>()
+    .register_rollback_resource::<RigidBodySetRes>()
+    .register_rollback_resource::<IslandManagerRes>()
+    .register_rollback_resource::<QueryPipelineRes>()
+    .register_rollback_resource::<ImpulseJointSetRes>()
+    .register_rollback_resource::<MultibodyJointSetRes>()
+    .register_rollback_resource::<IntegrationParametersRes>()
+    .register_rollback_resource::<RigidBodyRemovedEntitiesRes>()
+    .register_rollback_resource::<PhysicsEventsRes>()
+    .register_rollback_resource::<AnimationEventsRes>()
+    // register types of components you want to be rolled back
+    .register_rollback_component::<Ball2D>()
+    .register_rollback_component::<Player2D>()
+    .register_rollback_component::<PreviousInput2D>()
+    .register_rollback_component::<CharacterController2D>()
+    .register_rollback_component::<Transform2D>()
+    .register_rollback_component::<RigidBodyHandle2D>()
+    .register_rollback_component::<RigidBodyDesc2D>()
+    .register_rollback_component::<ColliderDesc2D>()
+    .register_rollback_component::<SpriteSheetAnimation>();
+
+    app.insert_resource(RollbackIdProvider::default())
+        .add_stage_before(CoreStage::Update, RollbackStageLabel, rollback_stage);
+
+    app.add_system_to_stage(CoreStage::Last, replay_save_system);
+
+    insert_game_resources(app, settings)
+}
+
+fn insert_game_resources<'a>(app: &'a mut App, settings: &GameSettings) -> &'a mut App {
+    app
+        //
+        .insert_resource(settings.clone())
+        //
+        .insert_resource(FrameCount::default())
+        .insert_resource(GravityRes::default())
+        .insert_resource(CCDSolverRes::default())
+        .insert_resource(BroadPhaseRes::default())
+        .insert_resource(ColliderSetRes::default())
+        .insert_resource(NarrowPhaseRes::default())
+        .insert_resource(RigidBodySetRes::default())
+        .insert_resource(IslandManagerRes::default())
+        .insert_resource(QueryPipelineRes::default())
+        .insert_resource(ImpulseJointSetRes::default())
+        .insert_resource(MultibodyJointSetRes::default())
+        .insert_resource(IntegrationParametersRes::new(settings.fps))
+        .insert_resource(RigidBodyRemovedEntitiesRes::default())
+        .insert_resource(PhysicsEventsRes::default())
+        .insert_resource(AnimationEventsRes::default())
+        .insert_resource(PhysicsPipelineRes::default())
+        //
+        .insert_resource(FrameInputsRes::default())
+        .insert_resource(
+            SnapshotHistoryRes::new(SNAPSHOT_HISTORY_FRAMES)
+                .with_resource::<FrameCount>()
+                .with_resource::<GravityRes>()
+                .with_resource::<CCDSolverRes>()
+                .with_resource::<BroadPhaseRes>()
+                .with_resource::<ColliderSetRes>()
+                .with_resource::<NarrowPhaseRes>()
+                .with_resource::<RigidBodySetRes>()
+                .with_resource::<IslandManagerRes>()
+                .with_resource::<QueryPipelineRes>()
+                .with_resource::<ImpulseJointSetRes>()
+                .with_resource::<MultibodyJointSetRes>()
+                .with_resource::<IntegrationParametersRes>()
+                .with_mapped_resource::<RigidBodyRemovedEntitiesRes>()
+                .with_mapped_resource::<PhysicsEventsRes>()
+                .with_mapped_resource::<AnimationEventsRes>()
+                .with_component::<Ball2D>()
+                .with_component::<Player2D>()
+                .with_component::<PreviousInput2D>()
+                .with_component::<CharacterController2D>()
+                .with_component::<Transform2D>()
+                .with_component::<RigidBodyHandle2D>()
+                .with_component::<RigidBodyDesc2D>()
+                .with_component::<ColliderDesc2D>()
+                .with_component::<SpriteSheetAnimation>(),
+        )
+        .insert_resource(NetworkStatusRes::default())
+        .add_event::<DesyncDetectedEvent>()
+        .add_system(network_events_system)
+        .add_system_to_stage(CoreStage::Last, desync_dump_system)
+        //
+        .add_startup_system(startup_level_system)
+        .add_startup_system(startup_ball_system.after(startup_level_system))
+        // rollback ids are handed out in spawn order, it must be the same on every peer
+        .add_startup_system(startup_player_system.after(startup_ball_system))
+}
+
+fn rollback_schedule() -> Schedule {
+    Schedule::default()
+        .with_stage(
+            RollbackStages::Game,
+            SystemStage::single_threaded()
+                .with_system(ball_system)
+                .with_system(frame_system)
+                .with_system(player_system)
+                .with_system(previous_input_system.after(player_system))
+                .with_system(player_animation_system.after(player_system))
+                .with_system(animate_sprite_system.after(player_animation_system)),
+        )
+        .with_stage_after(
+            RollbackStages::Game,
+            RollbackStages::Physics,
+            SystemStage::single_threaded()
+                .with_system(physics_system_remove.before(physics_system_create))
+                .with_system(physics_system_create.before(physics_system_add))
+                .with_system(physics_system_add.before(physics_system_step))
+                .with_system(physics_system_step),
+        )
+        .with_stage_after(
+            RollbackStages::Physics,
+            RollbackStages::Synchronization,
+            SystemStage::parallel()
+                .with_system(sync_transform_system)
+                .with_system(replay_record_system)
+                .with_system(rollback_diagnostics_system)
+                .with_system(synctest_checksum_system.at_end())
+                .with_system(snapshot_system.at_end().after(synctest_checksum_system)),
+        )
+}
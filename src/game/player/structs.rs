@@ -1,11 +1,19 @@
 use bevy::prelude::*;
 use ggrs::PlayerHandle;
+use serde::{Deserialize, Serialize};
 
 use crate::game::core::{
-    anim::structs::SpriteSheetAnimation, maths::structs::Transform2D, physics::structs::*,
+    anim::structs::SpriteSheetAnimation,
+    controller::structs::CharacterController2D,
+    input::structs::PreviousInput2D,
+    maths::structs::{Fixed, Transform2D},
+    physics::structs::*,
 };
 
-#[derive(Default, Reflect, Component)]
+/// Horizontal speed above which a frog plays the run clip without any input, in meters per second.
+pub const RUN_ANIMATION_MIN_SPEED: Fixed = Fixed::from_ratio(1, 2);
+
+#[derive(Default, Reflect, Component, Serialize, Deserialize)]
 pub struct Player2D {
     pub handle: PlayerHandle,
 }
@@ -13,8 +21,10 @@ pub struct Player2D {
 #[derive(Default, Bundle)]
 pub struct PlayerBundle {
     pub player: Player2D,
+    pub previous_input: PreviousInput2D,
+    pub character_controller: CharacterController2D,
     pub transform: Transform2D,
-    pub rigid_body_handle: RigidBodyHandle2D,
-    pub sprite_sheet: SpriteSheetBundle,
+    pub rigid_body_desc: RigidBodyDesc2D,
+    pub collider_desc: ColliderDesc2D,
     pub sprite_sheet_animation: SpriteSheetAnimation,
 }
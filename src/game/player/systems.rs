@@ -1,127 +1,286 @@
 use bevy::prelude::*;
-use bevy_ggrs::ggrs::PlayerHandle;
-use bevy_ggrs::{PlayerInputs, Rollback, RollbackIdProvider, Session};
-use log::info;
+use bevy_ggrs::{Rollback, RollbackIdProvider, Session};
 use rapier2d::prelude::*;
 
 use crate::game::{
-    core::physics::systems::{PLAYER_PHYSICS_GROUP, SOLID_PHYSICS_GROUP},
-    GameConfig,
+    core::physics::systems::{ONE_WAY_PHYSICS_GROUP, PLAYER_PHYSICS_GROUP, SOLID_PHYSICS_GROUP},
+    num_players, GameConfig,
 };
 use crate::game::{
     core::{
-        anim::{structs::SpriteSheetAnimation, utilities::speed_as_secs},
-        input::structs::{INPUT_JUMP, INPUT_LEFT, INPUT_RIGHT},
-        maths::structs::{Transform2D, Vector2D},
+        anim::structs::{AnimationState, SpriteSheetAnimation},
+        controller::structs::*,
+        controller::utilities::{move_and_slide, probe_ground, CharacterShape},
+        input::structs::{FrameInputsRes, PreviousInput2D, INPUT_DOWN, INPUT_JUMP},
+        level::structs::LevelRes,
+        maths::structs::Fixed,
         physics::structs::*,
     },
-    player::structs::{Player2D, PlayerBundle},
-    GAME_FPS,
+    player::structs::{Player2D, PlayerBundle, RUN_ANIMATION_MIN_SPEED},
 };
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn player_system(
-    inputs: Res<PlayerInputs<GameConfig>>,
+    inputs: Res<FrameInputsRes>,
+    collider_set: Res<ColliderSetRes>,
+    query_pipeline: Res<QueryPipelineRes>,
+    integration_parameters: Res<IntegrationParametersRes>,
     mut rigid_body_set: ResMut<RigidBodySetRes>,
     //
-    mut query: Query<(&Player2D, &RigidBodyHandle2D, &Children), With<Rollback>>,
+    mut query: Query<
+        (
+            &Player2D,
+            &PreviousInput2D,
+            &mut CharacterController2D,
+            &RigidBodyHandle2D,
+            Option<&Children>,
+        ),
+        With<Rollback>,
+    >,
     mut query_children_text: Query<&mut Text>,
 ) {
-    for (player, rigid_body_handle, children) in query.iter_mut() {
+    let dt = Fixed::from_f32(integration_parameters.dt);
+
+    for (player, previous_input, mut controller, rigid_body_handle, children) in query.iter_mut() {
         let (input, _) = inputs[player.handle];
-        let rigid_body = &mut rigid_body_set[rigid_body_handle.0];
 
-        for &child in children.iter() {
-            if let Ok(mut text) = query_children_text.get_mut(child) {
-                text.sections[0].value = format!("{:?} {:?}", player.handle, input.inp).to_string();
+        if let Some(children) = children {
+            for &child in children.iter() {
+                if let Ok(mut text) = query_children_text.get_mut(child) {
+                    text.sections[0].value =
+                        format!("{:?} {:?} {:?}", player.handle, input.inp, input.axis_x)
+                            .to_string();
+                }
             }
         }
 
-        if input.inp & INPUT_LEFT != 0 {
-            rigid_body.apply_impulse(vector!(-1.0, 0.0), true);
-        } else if input.inp & INPUT_RIGHT != 0 {
-            rigid_body.apply_impulse(vector!(1.0, 0.0), true);
-        } else if input.inp & INPUT_JUMP != 0 {
-            rigid_body.apply_impulse(vector!(0.0, 1.0), true);
+        let mut velocity = controller.velocity;
+
+        // a half tilted stick runs at half the speed
+        let target_speed = input.axis_x() * MAX_RUN_SPEED;
+        let acceleration = match (input.axis_x != 0, controller.grounded) {
+            (true, true) => RUN_ACCELERATION,
+            (true, false) => AIR_ACCELERATION,
+            (false, true) => GROUND_FRICTION,
+            (false, false) => AIR_FRICTION,
+        };
+        velocity.x = move_towards(velocity.x, target_speed, acceleration * dt);
+
+        if previous_input.just_pressed(input, INPUT_JUMP) {
+            controller.jump_buffer_frames = JUMP_BUFFER_FRAMES;
+        }
+        let can_jump = controller.grounded || controller.frames_since_grounded < COYOTE_FRAMES;
+        let jumped = controller.jump_buffer_frames > 0 && can_jump;
+        if jumped {
+            velocity.y = JUMP_SPEED;
+            controller.jump_buffer_frames = 0;
+            controller.frames_since_grounded = COYOTE_FRAMES;
+        } else {
+            controller.jump_buffer_frames = controller.jump_buffer_frames.saturating_sub(1);
+        }
+        // releasing the button early makes a shorter jump
+        if previous_input.just_released(input, INPUT_JUMP) && velocity.y > Fixed::ZERO {
+            velocity.y *= JUMP_CUT_FACTOR;
+        }
+
+        if !controller.grounded || jumped {
+            velocity.y = (velocity.y - CHARACTER_GRAVITY * dt).max(-MAX_FALL_SPEED);
+        }
+
+        let rigid_body = &rigid_body_set[rigid_body_handle.0];
+        let collider = &collider_set[rigid_body.colliders()[0]];
+        let character = CharacterShape {
+            shape: collider.shape(),
+            position: *rigid_body.position(),
+            filter: QueryFilter::new()
+                .exclude_rigid_body(rigid_body_handle.0)
+                .groups(InteractionGroups::new(
+                    PLAYER_PHYSICS_GROUP,
+                    SOLID_PHYSICS_GROUP | ONE_WAY_PHYSICS_GROUP,
+                )),
+            drop_through: input.pressed(INPUT_DOWN),
+        };
+
+        // rapier moves the character in float meters, the velocity stays in fixed point
+        let motion = velocity * dt;
+        let mut motion = vector![motion.x.to_f32(), motion.y.to_f32()];
+
+        // walk along the slope the character stands on
+        let ground_normal = if controller.grounded && !jumped {
+            probe_ground(
+                &query_pipeline,
+                &rigid_body_set,
+                &collider_set,
+                &character,
+                &Vector::zeros(),
+                GROUND_SNAP_DISTANCE,
+            )
+            .map(|(_, normal)| normal)
+        } else {
+            None
+        };
+        if let Some(normal) = ground_normal {
+            let tangent = vector![normal.y, -normal.x];
+            motion = tangent * motion.x;
         }
+
+        let mut movement = move_and_slide(
+            &query_pipeline,
+            &rigid_body_set,
+            &collider_set,
+            &character,
+            motion,
+        );
+
+        // stick to the ground when walking down a slope or a step
+        if ground_normal.is_some() && movement.ground_normal.is_none() {
+            if let Some((distance, normal)) = probe_ground(
+                &query_pipeline,
+                &rigid_body_set,
+                &collider_set,
+                &character,
+                &movement.translation,
+                GROUND_SNAP_DISTANCE,
+            ) {
+                movement.translation.y -= (distance - CHARACTER_SKIN).max(0.0);
+                movement.ground_normal = Some(normal);
+            }
+        }
+        if movement.ground_normal.is_none() && velocity.y <= Fixed::ZERO {
+            movement.ground_normal = probe_ground(
+                &query_pipeline,
+                &rigid_body_set,
+                &collider_set,
+                &character,
+                &movement.translation,
+                2.0 * CHARACTER_SKIN,
+            )
+            .map(|(_, normal)| normal);
+        }
+
+        controller.grounded = movement.ground_normal.is_some() && velocity.y <= Fixed::ZERO;
+        if controller.grounded {
+            controller.frames_since_grounded = 0;
+            velocity.y = Fixed::ZERO;
+        } else {
+            controller.frames_since_grounded = controller.frames_since_grounded.saturating_add(1);
+        }
+        if movement.hit_ceiling && velocity.y > Fixed::ZERO {
+            velocity.y = Fixed::ZERO;
+        }
+        if movement.hit_wall {
+            velocity.x = Fixed::ZERO;
+        }
+        controller.velocity = velocity;
+
+        let translation = rigid_body.translation() + movement.translation;
+        rigid_body_set[rigid_body_handle.0].set_next_kinematic_translation(translation);
+    }
+}
+
+/// Animation state machine of the frogs, driven by the controller state of the frame and the input.
+pub fn player_animation_system(
+    inputs: Res<FrameInputsRes>,
+    //
+    mut query: Query<
+        (&Player2D, &CharacterController2D, &mut SpriteSheetAnimation),
+        With<Rollback>,
+    >,
+) {
+    for (player, controller, mut animation) in query.iter_mut() {
+        let (input, _) = inputs[player.handle];
+
+        // face the pressed direction, or keep the last one when the stick is centered
+        if input.axis_x() < Fixed::ZERO {
+            animation.facing_left = true;
+        } else if input.axis_x() > Fixed::ZERO {
+            animation.facing_left = false;
+        }
+
+        let running = input.axis_x != 0 || controller.velocity.x.abs() > RUN_ANIMATION_MIN_SPEED;
+        let state = match animation.state {
+            _ if !controller.grounded && controller.velocity.y > Fixed::ZERO => {
+                AnimationState::Jump
+            }
+            _ if !controller.grounded => AnimationState::Fall,
+            AnimationState::Jump | AnimationState::Fall => AnimationState::Land,
+            // running cuts the landing short
+            AnimationState::Land if !animation.finished && !running => AnimationState::Land,
+            _ if running => AnimationState::Run,
+            _ => AnimationState::Idle,
+        };
+
+        animation.play(state);
     }
 }
 
 pub fn startup_player_system(
+    level: Res<LevelRes>,
     session: Res<Session<GameConfig>>,
-    asset_server: Res<AssetServer>,
     //
     mut commands: Commands,
-    mut textures: ResMut<Assets<TextureAtlas>>,
-    mut collider_set: ResMut<ColliderSetRes>,
-    mut rigid_body_set: ResMut<RigidBodySetRes>,
     mut rollback_id_provider: ResMut<RollbackIdProvider>,
 ) {
-    let num_players = match &*session {
-        Session::P2PSession(s) => s.num_players(),
-        Session::SyncTestSession(s) => s.num_players(),
-        Session::SpectatorSession(s) => s.num_players(),
-    };
-    let font_handle: Handle<Font> = asset_server.load("fonts/Pixellari.ttf");
-    let texture_handle: Handle<Image> = asset_server.load("textures/frog/Stand.png");
-
-    let transform = Transform2D::from_position(Vector2D::new(0.0, -10.0));
-    commands.spawn((
-        Transform::default(),
-        GlobalTransform::default(),
-        create_world_rigid_body(&transform, &mut collider_set, &mut rigid_body_set),
-        transform,
-    ));
+    let num_players = num_players(&session);
 
     for player_handle in 0..num_players {
-        let transform = Transform2D::from_position(Vector2D::new(player_handle as f32 * 5.0, 10.0));
-        let rigid_body_handle = create_player_rigid_body(
-            player_handle,
-            &transform,
-            &mut collider_set,
-            &mut rigid_body_set,
-        );
+        commands.spawn((
+            PlayerBundle {
+                player: Player2D {
+                    handle: player_handle,
+                },
+                previous_input: default(),
+                character_controller: default(),
+                transform: level.player_spawn(player_handle),
+                // moved by the character controller, not by the solver
+                rigid_body_desc: RigidBodyDesc2D::new(RigidBodyType::KinematicPositionBased),
+                collider_desc: ColliderDesc2D::new(ColliderShape2D::Cuboid {
+                    half_width: 0.5,
+                    half_height: 1.4,
+                })
+                .collision_groups(
+                    PLAYER_PHYSICS_GROUP,
+                    SOLID_PHYSICS_GROUP | PLAYER_PHYSICS_GROUP,
+                )
+                .active_events(ActiveEvents::COLLISION_EVENTS),
+                //
+                sprite_sheet_animation: default(),
+            },
+            rollback_id_provider.next(),
+        ));
+    }
+}
 
-        let is_local_player = match &*session {
-            Session::P2PSession(s) => s.local_player_handles().contains(&player_handle),
-            Session::SyncTestSession(_) => false,
-            Session::SpectatorSession(_) => false,
-        };
+pub fn player_sprite_system(
+    asset_server: Res<AssetServer>,
+    //
+    mut commands: Commands,
+    mut textures: ResMut<Assets<TextureAtlas>>,
+    //
+    query: Query<Entity, Added<Player2D>>,
+) {
+    if query.is_empty() {
+        return;
+    }
 
-        if is_local_player {
-            commands.spawn(Camera2dBundle::default());
-        }
+    let font_handle: Handle<Font> = asset_server.load("fonts/Pixellari.ttf");
+    let texture_handle: Handle<Image> = asset_server.load("textures/frog/Stand.png");
 
+    for entity in query.iter() {
         commands
-            .spawn((
-                PlayerBundle {
-                    player: Player2D {
-                        handle: player_handle,
-                    },
-                    transform,
-                    rigid_body_handle,
-                    //
-                    sprite_sheet: SpriteSheetBundle {
-                        texture_atlas: textures.add(TextureAtlas::from_grid(
-                            texture_handle.clone(),
-                            Vec2::new(38.0, 32.0),
-                            20,
-                            1,
-                            None,
-                            None,
-                        )),
-                        //
-                        ..default()
-                    },
-                    sprite_sheet_animation: SpriteSheetAnimation {
-                        speed: speed_as_secs(GAME_FPS as u32, 0.06),
-                        //
-                        ..default()
-                    },
-                    //
-                    ..default()
-                },
-                rollback_id_provider.next(),
-            ))
+            .entity(entity)
+            .insert(SpriteSheetBundle {
+                texture_atlas: textures.add(TextureAtlas::from_grid(
+                    texture_handle.clone(),
+                    Vec2::new(38.0, 32.0),
+                    20,
+                    1,
+                    None,
+                    None,
+                )),
+                //
+                ..default()
+            })
             //
             .with_children(|parent| {
                 parent.spawn(Text2dBundle {
@@ -144,60 +303,10 @@ pub fn startup_player_system(
     }
 }
 
-fn create_world_rigid_body(
-    transform: &Transform2D,
-    collider_set: &mut ColliderSetRes,
-    rigid_body_set: &mut RigidBodySetRes,
-) -> RigidBodyHandle2D {
-    let rigid_body = RigidBodyBuilder::fixed()
-        .rotation(transform.rotation)
-        .translation(vector![transform.position.x, transform.position.y])
-        .build();
-    let rigid_body_handle = rigid_body_set.insert(rigid_body);
-    let rigid_body_collider = ColliderBuilder::cuboid(20.0, 1.0)
-        .restitution(0.0)
-        .collision_groups(InteractionGroups::new(
-            SOLID_PHYSICS_GROUP,
-            SOLID_PHYSICS_GROUP | PLAYER_PHYSICS_GROUP,
-        ))
-        .build();
-
-    collider_set.insert_with_parent(rigid_body_collider, rigid_body_handle, rigid_body_set);
-
-    info!(
-        "create_world_rigid_body: rigid_body_handle: {:?}",
-        rigid_body_handle
-    );
-
-    RigidBodyHandle2D(rigid_body_handle)
-}
-
-fn create_player_rigid_body(
-    handle: PlayerHandle,
-    transform: &Transform2D,
-    collider_set: &mut ColliderSetRes,
-    rigid_body_set: &mut RigidBodySetRes,
-) -> RigidBodyHandle2D {
-    let rigid_body = RigidBodyBuilder::dynamic()
-        .rotation(transform.rotation)
-        .translation(vector![transform.position.x, transform.position.y])
-        .lock_rotations()
-        .build();
-    let rigid_body_handle = rigid_body_set.insert(rigid_body);
-    let rigid_body_collider = ColliderBuilder::cuboid(0.5, 1.4)
-        .restitution(0.0)
-        .collision_groups(InteractionGroups::new(
-            PLAYER_PHYSICS_GROUP,
-            SOLID_PHYSICS_GROUP | PLAYER_PHYSICS_GROUP,
-        ))
-        .build();
-
-    collider_set.insert_with_parent(rigid_body_collider, rigid_body_handle, rigid_body_set);
-
-    info!(
-        "create_player_rigid_body: player_handle: {}, rigid_body_handle: {:?}",
-        handle, rigid_body_handle
-    );
-
-    RigidBodyHandle2D(rigid_body_handle)
+fn move_towards(current: Fixed, target: Fixed, max_delta: Fixed) -> Fixed {
+    if (target - current).abs() <= max_delta {
+        target
+    } else {
+        current + (target - current).signum() * max_delta
+    }
 }
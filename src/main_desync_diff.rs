@@ -0,0 +1,209 @@
+use ron::Value;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use structopt::StructOpt;
+
+/// Mirror of the `FrameDump` written by the game on desync.
+#[derive(Deserialize)]
+struct FrameDump {
+    frame: u32,
+    resources: BTreeMap<String, Value>,
+    entities: BTreeMap<u32, BTreeMap<String, Value>>,
+}
+
+struct Difference {
+    path: String,
+    left: Option<Value>,
+    right: Option<Value>,
+}
+
+#[derive(StructOpt)]
+struct CommandLineArgs {
+    #[structopt(parse(from_os_str))]
+    left: PathBuf,
+    #[structopt(parse(from_os_str))]
+    right: PathBuf,
+    #[structopt(long, default_value = "20")]
+    max_differences: usize,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cmd = CommandLineArgs::from_args();
+    let left = load_dump(&cmd.left)?;
+    let right = load_dump(&cmd.right)?;
+
+    let mut compared_frames = 0;
+    for (frame, left_dump) in left.iter() {
+        let right_dump = match right.get(frame) {
+            Some(right_dump) => right_dump,
+            None => continue,
+        };
+
+        let differences = diff_frame(left_dump, right_dump);
+        compared_frames += 1;
+
+        if !differences.is_empty() {
+            println!(
+                "first divergence at frame {}, {} differences:",
+                frame,
+                differences.len()
+            );
+            for difference in differences.iter().take(cmd.max_differences) {
+                println!(
+                    "\t{}: {} != {}",
+                    difference.path,
+                    format_value(&difference.left),
+                    format_value(&difference.right)
+                );
+            }
+            if differences.len() > cmd.max_differences {
+                println!("\t...");
+            }
+            process::exit(1);
+        }
+    }
+
+    if compared_frames == 0 {
+        return Err("the dumps have no frame in common".into());
+    }
+    println!("no divergence in {} common frames", compared_frames);
+
+    Ok(())
+}
+
+fn load_dump(path: &Path) -> Result<BTreeMap<u32, FrameDump>, Box<dyn Error>> {
+    let dumps: Vec<FrameDump> = ron::from_str(&fs::read_to_string(path)?)?;
+
+    Ok(dumps.into_iter().map(|dump| (dump.frame, dump)).collect())
+}
+
+fn diff_frame(left: &FrameDump, right: &FrameDump) -> Vec<Difference> {
+    let mut differences = Vec::new();
+
+    diff_maps("", &left.resources, &right.resources, &mut differences);
+
+    let rollback_ids: BTreeSet<_> = left.entities.keys().chain(right.entities.keys()).collect();
+    for rollback_id in rollback_ids {
+        let path = format!("Rollback({})", rollback_id);
+
+        match (
+            left.entities.get(rollback_id),
+            right.entities.get(rollback_id),
+        ) {
+            (Some(left), Some(right)) => diff_maps(&path, left, right, &mut differences),
+            (left, right) => differences.push(Difference {
+                path,
+                left: left.map(|_| Value::Unit),
+                right: right.map(|_| Value::Unit),
+            }),
+        }
+    }
+
+    differences
+}
+
+fn diff_maps(
+    path: &str,
+    left: &BTreeMap<String, Value>,
+    right: &BTreeMap<String, Value>,
+    differences: &mut Vec<Difference>,
+) {
+    let names: BTreeSet<_> = left.keys().chain(right.keys()).collect();
+
+    for name in names {
+        let path = join_path(path, name);
+
+        match (left.get(name), right.get(name)) {
+            (Some(left), Some(right)) => diff_values(path, left, right, differences),
+            (left, right) => differences.push(Difference {
+                path,
+                left: left.cloned(),
+                right: right.cloned(),
+            }),
+        }
+    }
+}
+
+fn diff_values(path: String, left: &Value, right: &Value, differences: &mut Vec<Difference>) {
+    if left == right {
+        return;
+    }
+
+    match (left, right) {
+        (Value::Map(left), Value::Map(right)) => {
+            let left: BTreeMap<_, _> = left.iter().collect();
+            let right: BTreeMap<_, _> = right.iter().collect();
+            let keys: BTreeSet<_> = left.keys().chain(right.keys()).collect();
+
+            for key in keys {
+                let path = join_path(&path, &format_key(key));
+
+                match (left.get(key), right.get(key)) {
+                    (Some(left), Some(right)) => diff_values(path, left, right, differences),
+                    (left, right) => differences.push(Difference {
+                        path,
+                        left: left.map(|&value| value.clone()),
+                        right: right.map(|&value| value.clone()),
+                    }),
+                }
+            }
+        }
+        (Value::Seq(left), Value::Seq(right)) => {
+            for index in 0..left.len().max(right.len()) {
+                let path = format!("{}[{}]", path, index);
+
+                match (left.get(index), right.get(index)) {
+                    (Some(left), Some(right)) => diff_values(path, left, right, differences),
+                    (left, right) => differences.push(Difference {
+                        path,
+                        left: left.cloned(),
+                        right: right.cloned(),
+                    }),
+                }
+            }
+        }
+        (Value::Option(Some(left)), Value::Option(Some(right))) => {
+            diff_values(path, left, right, differences)
+        }
+        _ => differences.push(Difference {
+            path,
+            left: Some(left.clone()),
+            right: Some(right.clone()),
+        }),
+    }
+}
+
+fn join_path(path: &str, name: &str) -> String {
+    if path.is_empty() {
+        name.to_owned()
+    } else {
+        format!("{}.{}", path, name)
+    }
+}
+
+fn format_key(key: &Value) -> String {
+    match key {
+        Value::String(key) => key.clone(),
+        key => format!("[{}]", ron::to_string(key).unwrap_or_default()),
+    }
+}
+
+fn format_value(value: &Option<Value>) -> String {
+    const MAX_LENGTH: usize = 80;
+
+    match value {
+        Some(value) => {
+            let value = ron::to_string(value).unwrap_or_default();
+            if value.chars().count() > MAX_LENGTH {
+                format!("{}...", value.chars().take(MAX_LENGTH).collect::<String>())
+            } else {
+                value
+            }
+        }
+        None => "<missing>".to_owned(),
+    }
+}
@@ -2,11 +2,17 @@ mod game;
 
 use bevy::app::App;
 use bevy_ggrs::Session;
-use ggrs::{PlayerType, SessionBuilder, UdpNonBlockingSocket};
+use ggrs::{DesyncDetection, PlayerType, SessionBuilder, UdpNonBlockingSocket};
 use std::error::Error;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
+use std::path::PathBuf;
 use structopt::StructOpt;
 
+use crate::game::core::level::structs::LevelRes;
+use crate::game::core::network::socket::SimulatedNetworkArgs;
+use crate::game::core::network::utilities::{exchange_session_checks, join_lobby};
+use crate::game::core::replay::structs::ReplayRecorderRes;
+use crate::game::core::settings::structs::GameSettingsArgs;
 use crate::game::{GameApp, GameConfig};
 
 #[derive(StructOpt)]
@@ -15,39 +21,126 @@ struct CommandLineArgs {
     port: u16,
     #[structopt(long)]
     players: Vec<String>,
+    #[structopt(long)]
+    spectators: Vec<SocketAddr>,
+    #[structopt(long)]
+    lobby: Option<SocketAddr>,
+    #[structopt(long, default_value = "frogrs")]
+    room: String,
+    #[structopt(long, default_value = "2")]
+    room_size: usize,
+    /// IP the other players of the lobby room reach this game on, the one seen by the lobby when missing
+    #[structopt(long)]
+    advertise: Option<IpAddr>,
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+    #[structopt(flatten)]
+    settings: GameSettingsArgs,
+    #[structopt(flatten)]
+    simulated_network: SimulatedNetworkArgs,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cmd = CommandLineArgs::from_args();
-    let num_players = cmd.players.len();
+    let settings = cmd.settings.load()?;
+    let level = settings.load_level()?;
+    // the logger only starts with the app
+    eprintln!("level {}, checksum {:016x}", level.name, level.checksum());
+
+    // either ask the lobby for the players or take them from the command line
+    let players: Vec<PlayerType<SocketAddr>> = match cmd.lobby {
+        Some(lobby_addr) => {
+            let (local_handle, addrs) = join_lobby(
+                lobby_addr,
+                &cmd.room,
+                cmd.room_size,
+                cmd.port,
+                level.checksum(),
+                settings.fps,
+                cmd.advertise,
+            )?;
+            addrs
+                .into_iter()
+                .enumerate()
+                .map(|(i, addr)| {
+                    if i == local_handle {
+                        PlayerType::Local
+                    } else {
+                        PlayerType::Remote(addr)
+                    }
+                })
+                .collect()
+        }
+        None => cmd
+            .players
+            .iter()
+            .map(|player_addr| match player_addr.as_str() {
+                "local" => Ok(PlayerType::Local),
+                player_addr => Ok(PlayerType::Remote(player_addr.parse()?)),
+            })
+            .collect::<Result<_, Box<dyn Error>>>()?,
+    };
+    let num_players = players.len();
     assert!(num_players > 0);
 
+    // peers on another level or frame rate would desync, check them before GGRS takes the port
+    let peers: Vec<SocketAddr> = players
+        .iter()
+        .filter_map(|player_type| match player_type {
+            PlayerType::Remote(addr) => Some(*addr),
+            _ => None,
+        })
+        .chain(cmd.spectators.iter().copied())
+        .collect();
+    exchange_session_checks(cmd.port, &peers, level.checksum(), settings.fps)?;
+
     // create a GGRS session
     let mut session_builder = SessionBuilder::<GameConfig>::new()
         .with_num_players(num_players)
-        .with_input_delay(2)
-        .with_max_prediction_window(12);
+        .with_fps(settings.fps)?
+        .with_input_delay(settings.input_delay)
+        .with_max_prediction_window(settings.max_prediction_window)
+        .with_disconnect_timeout(settings.disconnect_timeout())
+        .with_disconnect_notify_delay(settings.disconnect_notify_delay())
+        .with_desync_detection_mode(match settings.desync_detection_interval {
+            0 => DesyncDetection::Off,
+            interval => DesyncDetection::On { interval },
+        });
 
     // add players
-    for (i, player_addr) in cmd.players.iter().enumerate() {
-        if player_addr == "local" {
-            // local player
-            session_builder = session_builder.add_player(PlayerType::Local, i)?;
-        } else {
-            // remote player
-            let remote_addr: SocketAddr = player_addr.parse()?;
-            session_builder = session_builder.add_player(PlayerType::Remote(remote_addr), i)?;
-        }
+    for (i, player_type) in players.into_iter().enumerate() {
+        session_builder = session_builder.add_player(player_type, i)?;
+    }
+
+    // add spectators, their handles come after the players
+    for (i, spectator_addr) in cmd.spectators.iter().enumerate() {
+        session_builder =
+            session_builder.add_player(PlayerType::Spectator(*spectator_addr), num_players + i)?;
     }
 
     // start the GGRS session
     let socket = UdpNonBlockingSocket::bind_to_port(cmd.port)?;
-    let session = session_builder.start_p2p_session(socket)?;
+    let session = if cmd.simulated_network.is_enabled() {
+        let socket = cmd.simulated_network.wrap(socket, cmd.port as u64)?;
+        session_builder.start_p2p_session(socket)?
+    } else {
+        session_builder.start_p2p_session(socket)?
+    };
+
+    let mut app = App::new();
+    if let Some(path) = cmd.record {
+        app.insert_resource(ReplayRecorderRes::new(
+            path,
+            num_players,
+            level.checksum(),
+            settings.fps,
+        ));
+    }
+    app.insert_game("frogrs", &settings)
+        .insert_resource(LevelRes(level))
+        .insert_resource(Session::P2PSession(session));
 
-    App::new()
-        .insert_game("frogrs")
-        .insert_resource(Session::P2PSession(session))
-        .run();
+    app.run();
 
     Ok(())
 }
@@ -0,0 +1,197 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::net::{IpAddr, SocketAddr, TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use structopt::StructOpt;
+
+/// Players waiting in a room, in join order which is also their player handle order.
+struct Room {
+    size: usize,
+    level_checksum: u64,
+    fps: usize,
+    members: Vec<(TcpStream, SocketAddr)>,
+}
+
+type Rooms = Arc<Mutex<HashMap<String, Room>>>;
+
+#[derive(StructOpt)]
+struct CommandLineArgs {
+    #[structopt(long, default_value = "7777")]
+    port: u16,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cmd = CommandLineArgs::from_args();
+    let listener = TcpListener::bind(("0.0.0.0", cmd.port))?;
+    let rooms: Rooms = Arc::new(Mutex::new(HashMap::new()));
+
+    println!("lobby listening on port {}", cmd.port);
+
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let rooms = rooms.clone();
+
+        thread::spawn(move || {
+            let peer_addr = stream.peer_addr().ok();
+            if let Err(e) = handle_client(stream, &rooms) {
+                println!("client {:?} error: {}", peer_addr, e);
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Handles a `JOIN <room> <room size> <game port> <level checksum> <fps> [<advertised ip>]` request, see `join_lobby` in the game.
+fn handle_client(mut stream: TcpStream, rooms: &Rooms) -> Result<(), Box<dyn Error>> {
+    let mut line = String::new();
+    BufReader::new(stream.try_clone()?).read_line(&mut line)?;
+
+    let tokens: Vec<&str> = line.split_whitespace().collect();
+    let (room_name, room_size, port, level_checksum, fps, advertised_ip) = match tokens.as_slice() {
+        ["JOIN", room_name, room_size, port, level_checksum, fps, advertised_ip @ ..]
+            if advertised_ip.len() <= 1 =>
+        {
+            (
+                room_name.to_string(),
+                room_size.parse::<usize>()?,
+                port.parse::<u16>()?,
+                u64::from_str_radix(level_checksum, 16)?,
+                fps.parse::<usize>()?,
+                advertised_ip
+                    .first()
+                    .map(|ip| ip.parse::<IpAddr>())
+                    .transpose()?,
+            )
+        }
+        _ => {
+            writeln!(stream, "ERROR invalid request")?;
+            return Ok(());
+        }
+    };
+    if room_size == 0 {
+        writeln!(stream, "ERROR invalid room size")?;
+        return Ok(());
+    }
+
+    // without an advertised address the game is reached on the address it reached the lobby from
+    let game_ip = match advertised_ip {
+        Some(ip) => ip,
+        None => stream.peer_addr()?.ip(),
+    };
+    let game_addr = SocketAddr::new(game_ip, port);
+
+    let mut rooms = rooms.lock().unwrap();
+    let room = rooms.entry(room_name.clone()).or_insert_with(|| Room {
+        size: room_size,
+        level_checksum,
+        fps,
+        members: Vec::new(),
+    });
+
+    // a crashed client must not keep its slot
+    room.members.retain(|(member_stream, member_addr)| {
+        let connected = is_connected(member_stream);
+        if !connected {
+            println!("{} left room {}", member_addr, room_name);
+        }
+        connected
+    });
+    // a room left by all its members is free again
+    if room.members.is_empty() {
+        room.size = room_size;
+        room.level_checksum = level_checksum;
+        room.fps = fps;
+    }
+    if room.size != room_size {
+        writeln!(
+            stream,
+            "ERROR room {} is for {} players",
+            room_name, room.size
+        )?;
+        return Ok(());
+    }
+    if room.level_checksum != level_checksum {
+        writeln!(stream, "ERROR room {} plays another level", room_name)?;
+        return Ok(());
+    }
+    // the simulation steps by one frame, peers running at another rate drift apart
+    if room.fps != fps {
+        writeln!(
+            stream,
+            "ERROR room {} simulates {} frames per second",
+            room_name, room.fps
+        )?;
+        return Ok(());
+    }
+
+    // a loopback address only reaches the players running on the same machine
+    if room
+        .members
+        .iter()
+        .any(|(_, member_addr)| member_addr.ip().is_loopback() != game_ip.is_loopback())
+    {
+        writeln!(
+            stream,
+            "ERROR {} cannot reach the other players of room {}, join with --advertise",
+            game_addr, room_name
+        )?;
+        return Ok(());
+    }
+    if room
+        .members
+        .iter()
+        .any(|(_, member_addr)| *member_addr == game_addr)
+    {
+        writeln!(
+            stream,
+            "ERROR {} already joined room {}",
+            game_addr, room_name
+        )?;
+        return Ok(());
+    }
+
+    println!(
+        "{} joined room {} as player {}",
+        game_addr,
+        room_name,
+        room.members.len()
+    );
+    room.members.push((stream, game_addr));
+
+    if room.members.len() == room.size {
+        let room = rooms.remove(&room_name).unwrap();
+        let addrs = room
+            .members
+            .iter()
+            .map(|(_, addr)| addr.to_string())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        println!("room {} is full, starting: {}", room_name, addrs);
+        for (handle, (mut member_stream, member_addr)) in room.members.into_iter().enumerate() {
+            if let Err(e) = writeln!(member_stream, "START {} {}", handle, addrs) {
+                println!("failed to start {}: {}", member_addr, e);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Whether the client waiting on `stream` is still there, it sends nothing until the room starts.
+fn is_connected(stream: &TcpStream) -> bool {
+    if stream.set_nonblocking(true).is_err() {
+        return false;
+    }
+    let connected = match stream.peek(&mut [0; 1]) {
+        // a closed connection reads as the end of the stream
+        Ok(0) => false,
+        Ok(_) => true,
+        Err(e) => e.kind() == ErrorKind::WouldBlock,
+    };
+
+    stream.set_nonblocking(false).is_ok() && connected
+}
@@ -0,0 +1,72 @@
+mod game;
+
+use bevy::app::App;
+use bevy_ggrs::Session;
+use ggrs::{PlayerType, SessionBuilder};
+use log::info;
+use std::error::Error;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+use crate::game::core::level::structs::LevelRes;
+use crate::game::core::replay::structs::Replay;
+use crate::game::core::settings::structs::GameSettingsArgs;
+use crate::game::{GameApp, GameConfig};
+
+#[derive(StructOpt)]
+struct CommandLineArgs {
+    #[structopt(parse(from_os_str))]
+    replay: PathBuf,
+    #[structopt(flatten)]
+    settings: GameSettingsArgs,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cmd = CommandLineArgs::from_args();
+    let settings = cmd.settings.load()?;
+    let replay = Replay::read(&cmd.replay)?;
+    let level = settings.load_level()?;
+    if replay.level_checksum != level.checksum() {
+        return Err(format!(
+            "{} was recorded on another level than {}, checksum {:016x} instead of {:016x}",
+            cmd.replay.display(),
+            level.name,
+            replay.level_checksum,
+            level.checksum()
+        )
+        .into());
+    }
+    // the physics steps by one frame, another frame rate simulates another game
+    if replay.fps != settings.fps {
+        return Err(format!(
+            "{} was recorded at {} frames per second instead of {}, replay it with --fps {}",
+            cmd.replay.display(),
+            replay.fps,
+            settings.fps,
+            replay.fps
+        )
+        .into());
+    }
+    let num_players = replay.num_players;
+
+    // the session is never advanced, it only tells the game which players to spawn
+    let mut session_builder = SessionBuilder::<GameConfig>::new().with_num_players(num_players);
+    for i in 0..num_players {
+        session_builder = session_builder.add_player(PlayerType::Local, i)?;
+    }
+    let session = session_builder.start_synctest_session()?;
+
+    info!(
+        "replaying {} frames of {} players",
+        replay.inputs.len(),
+        num_players
+    );
+
+    App::new()
+        .insert_replay_game("frogrs_replay", &settings, replay)
+        .insert_resource(LevelRes(level))
+        .insert_resource(Session::SyncTestSession(session))
+        .run();
+
+    Ok(())
+}
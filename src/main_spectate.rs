@@ -0,0 +1,56 @@
+mod game;
+
+use bevy::app::App;
+use bevy_ggrs::Session;
+use ggrs::{SessionBuilder, UdpNonBlockingSocket};
+use std::error::Error;
+use std::net::SocketAddr;
+use structopt::StructOpt;
+
+use crate::game::core::level::structs::LevelRes;
+use crate::game::core::network::utilities::exchange_session_checks;
+use crate::game::core::settings::structs::GameSettingsArgs;
+use crate::game::{GameApp, GameConfig};
+
+/// Watches a session from the address its host registered with --spectators, with the level and the frame rate
+/// of the host.
+#[derive(StructOpt)]
+struct CommandLineArgs {
+    #[structopt(long)]
+    port: u16,
+    #[structopt(long)]
+    host: SocketAddr,
+    #[structopt(long)]
+    players: usize,
+    #[structopt(flatten)]
+    settings: GameSettingsArgs,
+}
+
+fn main() -> Result<(), Box<dyn Error>> {
+    let cmd = CommandLineArgs::from_args();
+    let settings = cmd.settings.load()?;
+    let level = settings.load_level()?;
+    let num_players = cmd.players;
+    assert!(num_players > 0);
+
+    // create a GGRS session
+    let session_builder = SessionBuilder::<GameConfig>::new()
+        .with_num_players(num_players)
+        .with_fps(settings.fps)?;
+
+    // the logger only starts with the app
+    eprintln!("level {}, checksum {:016x}", level.name, level.checksum());
+    exchange_session_checks(cmd.port, &[cmd.host], level.checksum(), settings.fps)?;
+
+    // start the GGRS session, the host must have registered this address as a spectator
+    let socket = UdpNonBlockingSocket::bind_to_port(cmd.port)?;
+    let session = session_builder.start_spectator_session(cmd.host, socket);
+
+    App::new()
+        .insert_game("frogrs_spectate", &settings)
+        .insert_resource(LevelRes(level))
+        .insert_resource(Session::SpectatorSession(session))
+        .run();
+
+    Ok(())
+}
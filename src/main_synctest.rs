@@ -3,9 +3,20 @@ mod game;
 use bevy::app::App;
 use bevy_ggrs::Session;
 use ggrs::{PlayerType, SessionBuilder};
+use log::info;
+use simplelog::{Config, LevelFilter, SimpleLogger};
 use std::error::Error;
+use std::path::PathBuf;
+use std::process;
+use std::time::Instant;
 use structopt::StructOpt;
 
+use crate::game::core::frame::structs::FrameCount;
+use crate::game::core::input::structs::ScriptedInputRes;
+use crate::game::core::level::structs::LevelRes;
+use crate::game::core::replay::structs::ReplayRecorderRes;
+use crate::game::core::settings::structs::GameSettingsArgs;
+use crate::game::core::synctest::structs::SyncTestChecksumsRes;
 use crate::game::{GameApp, GameConfig};
 
 #[derive(StructOpt)]
@@ -14,18 +25,35 @@ struct CommandLineArgs {
     players: usize,
     #[structopt(long)]
     check_distance: usize,
+    #[structopt(long)]
+    headless: bool,
+    #[structopt(long, default_value = "600")]
+    frames: u32,
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+    #[structopt(long, parse(from_os_str))]
+    script: Option<PathBuf>,
+    #[structopt(long, parse(from_os_str))]
+    record: Option<PathBuf>,
+    #[structopt(flatten)]
+    settings: GameSettingsArgs,
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cmd = CommandLineArgs::from_args();
+    let settings = cmd.settings.load()?;
+    let level = settings.load_level()?;
+    let level_checksum = level.checksum();
     let num_players = cmd.players;
     assert!(num_players > 0);
 
     // create a GGRS session
     let mut session_builder = SessionBuilder::<GameConfig>::new()
         .with_num_players(num_players)
-        .with_input_delay(2)
-        .with_max_prediction_window(cmd.check_distance);
+        .with_fps(settings.fps)?
+        .with_input_delay(settings.input_delay)
+        .with_max_prediction_window(settings.max_prediction_window)
+        .with_check_distance(cmd.check_distance);
 
     // add players
     for i in 0..cmd.players {
@@ -35,10 +63,77 @@ fn main() -> Result<(), Box<dyn Error>> {
     // start the GGRS session
     let session = session_builder.start_synctest_session()?;
 
-    App::new()
-        .insert_game("frogrs_synctest")
-        .insert_resource(Session::SyncTestSession(session))
-        .run();
+    let replay_recorder = cmd
+        .record
+        .map(|path| ReplayRecorderRes::new(path, num_players, level_checksum, settings.fps));
+
+    if cmd.headless {
+        // bevy's log plugin is not part of the minimal plugins
+        SimpleLogger::init(LevelFilter::Info, Config::default())?;
+        info!("level {}, checksum {:016x}", level.name, level_checksum);
+
+        let scripted_input = match &cmd.script {
+            Some(path) => ScriptedInputRes::from_file(path)?,
+            None => ScriptedInputRes::from_seed(cmd.seed),
+        };
+
+        let mut app = App::new();
+        app.insert_headless_game(&settings)
+            .insert_resource(LevelRes(level))
+            .insert_resource(scripted_input)
+            .insert_resource(SyncTestChecksumsRes::default())
+            .insert_resource(Session::SyncTestSession(session));
+        if let Some(replay_recorder) = replay_recorder {
+            app.insert_resource(replay_recorder);
+        }
+
+        let start = Instant::now();
+        let mismatch = loop {
+            app.update();
+
+            if let Some(mismatch) = &app.world.resource::<SyncTestChecksumsRes>().mismatch {
+                break Some(mismatch.clone());
+            }
+            if app.world.resource::<FrameCount>().frame >= cmd.frames {
+                break None;
+            }
+        };
+
+        // the app never exits by itself, save the replay here
+        if let Some(replay_recorder) = app.world.get_resource::<ReplayRecorderRes>() {
+            replay_recorder.save()?;
+        }
+
+        if let Some(mismatch) = mismatch {
+            eprintln!(
+                "synctest failed: checksum mismatch at frame {}, expected {:#018x}, got {:#018x}",
+                mismatch.frame, mismatch.expected, mismatch.actual
+            );
+            process::exit(1);
+        }
+
+        let frames = app.world.resource::<FrameCount>().frame;
+        let elapsed = start.elapsed().as_secs_f64();
+        println!(
+            "synctest passed: {} frames, {} players, {:.2} s, {:.0} frames/s",
+            frames,
+            num_players,
+            elapsed,
+            frames as f64 / elapsed
+        );
+
+        return Ok(());
+    }
+
+    let mut app = App::new();
+    app.insert_game("frogrs_synctest", &settings)
+        .insert_resource(LevelRes(level))
+        .insert_resource(Session::SyncTestSession(session));
+    if let Some(replay_recorder) = replay_recorder {
+        app.insert_resource(replay_recorder);
+    }
+
+    app.run();
 
     Ok(())
 }